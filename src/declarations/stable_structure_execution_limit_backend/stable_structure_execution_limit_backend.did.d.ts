@@ -1,23 +1,65 @@
 import type { Principal } from "@dfinity/principal";
 import type { ActorMethod } from "@dfinity/agent";
 
+export interface Admin {
+  created_at: bigint;
+}
+export interface AssetEncodingNoContent {
+  modified: bigint;
+  sha256: Uint8Array | number[];
+  total_length: bigint;
+}
+export interface AssetKey {
+  token: [] | [string];
+  collection: string;
+  owner: Principal;
+  name: string;
+  description: [] | [string];
+  full_path: string;
+}
+export interface AssetNoContent {
+  key: AssetKey;
+  updated_at: bigint;
+  encodings: Array<[string, AssetEncodingNoContent]>;
+  headers: Array<[string, string]>;
+  created_at: bigint;
+}
 export interface CommitBatch {
   batch_id: bigint;
   headers: Array<[string, string]>;
+  compress: [] | [boolean];
   chunk_ids: Array<bigint>;
 }
+export type CommitBatchResult =
+  | {
+      Continue: { total_chunks: bigint; committed_chunks: bigint };
+    }
+  | { Done: null };
+export interface Config {
+  storage: StorageConfig;
+}
+export type DeleteAssetsResult =
+  | {
+      Continue: { deleted_assets: bigint };
+    }
+  | { Done: { deleted_assets: bigint } };
 export interface HttpRequest {
   url: string;
   method: string;
   body: Uint8Array | number[];
   headers: Array<[string, string]>;
+  certificate_version: [] | [number];
 }
 export interface HttpResponse {
   body: Uint8Array | number[];
   headers: Array<[string, string]>;
+  upgrade: [] | [boolean];
   streaming_strategy: [] | [StreamingStrategy];
   status_code: number;
 }
+export interface InitArgs {
+  admins: Array<Principal>;
+}
 export interface InitAssetKey {
   token: [] | [string];
   collection: string;
@@ -29,6 +71,86 @@ export interface InitAssetKey {
 export interface InitUploadResult {
   batch_id: bigint;
 }
+export interface ListMatcher {
+  key: [] | [string];
+  description: [] | [string];
+}
+export interface ListOrder {
+  field: ListOrderField;
+  desc: boolean;
+}
+export type ListOrderField =
+  | { UpdatedAt: null }
+  | { Keys: null }
+  | { CreatedAt: null };
+export interface ListPaginate {
+  start_after: [] | [string];
+  limit: [] | [bigint];
+}
+export interface ListParams {
+  order: [] | [ListOrder];
+  owner: [] | [Principal];
+  matcher: [] | [ListMatcher];
+  paginate: [] | [ListPaginate];
+}
+export interface ListResults {
+  matches_pages: [] | [bigint];
+  matches_length: bigint;
+  items_page: [] | [bigint];
+  items: Array<[string, AssetNoContent]>;
+  items_length: bigint;
+}
+export type MigrateAssetsResult =
+  | {
+      Continue: { migrated_assets: bigint };
+    }
+  | { Done: { migrated_assets: bigint } };
+export type Permission =
+  | { Controllers: null }
+  | { Private: null }
+  | { Public: null }
+  | { Managed: null };
+export type Result =
+  | { Ok: null }
+  | { Err: string };
+export type Result_1 =
+  | { Ok: Array<[string, Rule]> }
+  | { Err: string };
+export interface Rule {
+  updated_at: bigint;
+  max_size: [] | [bigint];
+  read: Permission;
+  created_at: bigint;
+  max_items: [] | [bigint];
+  write: Permission;
+}
+export type RulesType =
+  | { Db: null }
+  | { Storage: null };
+export interface SetRule {
+  max_size: [] | [bigint];
+  read: Permission;
+  max_items: [] | [bigint];
+  write: Permission;
+}
+export interface StorageConfig {
+  cors: Array<[string, StorageConfigCorsRule]>;
+  rewrites: Array<[string, string]>;
+  headers: Array<[string, Array<[string, string]>]>;
+  error_pages: Array<[number, string]>;
+  redirects: Array<[string, StorageConfigRedirect]>;
+}
+export interface StorageConfigCorsRule {
+  allow_methods: Array<string>;
+  expose_headers: Array<string>;
+  allow_origins: Array<string>;
+  allow_headers: Array<string>;
+  max_age: [] | [number];
+}
+export interface StorageConfigRedirect {
+  status_code: number;
+  location: string;
+}
 export interface StreamingCallbackHttpResponse {
   token: [] | [StreamingCallbackToken];
   body: Uint8Array | number[];
@@ -55,14 +177,34 @@ export interface UploadChunk {
 export interface UploadChunkResult {
   chunk_id: bigint;
 }
+export type WritePolicy =
+  | { Controllers: null }
+  | { Authenticated: null }
+  | { Owner: null };
 export interface _SERVICE {
-  commit_asset_upload: ActorMethod<[CommitBatch], undefined>;
+  add_admin: ActorMethod<[Principal], Result>;
+  commit_asset_upload: ActorMethod<[CommitBatch], CommitBatchResult>;
+  del_admin: ActorMethod<[Principal], undefined>;
+  del_asset: ActorMethod<[string, string], undefined>;
+  del_assets: ActorMethod<[string], DeleteAssetsResult>;
+  del_assets_with_prefix: ActorMethod<[string, string], DeleteAssetsResult>;
+  del_rule: ActorMethod<[RulesType, string], Result>;
+  get_config: ActorMethod<[], Config>;
+  get_write_policy: ActorMethod<[], WritePolicy>;
   http_request: ActorMethod<[HttpRequest], HttpResponse>;
   http_request_streaming_callback: ActorMethod<
     [StreamingCallbackToken],
     StreamingCallbackHttpResponse
   >;
+  http_request_update: ActorMethod<[HttpRequest], HttpResponse>;
   init_asset_upload: ActorMethod<[InitAssetKey], InitUploadResult>;
+  list_admins: ActorMethod<[], Array<[Principal, Admin]>>;
+  list_assets: ActorMethod<[string, ListParams], ListResults>;
+  list_rules: ActorMethod<[RulesType], Result_1>;
+  migrate_assets: ActorMethod<[], MigrateAssetsResult>;
+  set_config: ActorMethod<[Config], Result>;
+  set_rule: ActorMethod<[RulesType, string, SetRule], Result>;
+  set_write_policy: ActorMethod<[WritePolicy], undefined>;
   upload_asset_chunk: ActorMethod<[UploadChunk], UploadChunkResult>;
   version: ActorMethod<[], string>;
 }
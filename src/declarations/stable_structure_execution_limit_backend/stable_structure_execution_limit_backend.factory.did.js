@@ -1,15 +1,57 @@
 // @ts-ignore
 export const idlFactory = ({ IDL }) => {
+  const InitArgs = IDL.Record({ admins: IDL.Vec(IDL.Principal) });
+  const Result = IDL.Variant({ Ok: IDL.Null, Err: IDL.Text });
   const CommitBatch = IDL.Record({
     batch_id: IDL.Nat,
     headers: IDL.Vec(IDL.Tuple(IDL.Text, IDL.Text)),
+    compress: IDL.Opt(IDL.Bool),
     chunk_ids: IDL.Vec(IDL.Nat),
   });
+  const CommitBatchResult = IDL.Variant({
+    Continue: IDL.Record({
+      total_chunks: IDL.Nat64,
+      committed_chunks: IDL.Nat64,
+    }),
+    Done: IDL.Null,
+  });
+  const DeleteAssetsResult = IDL.Variant({
+    Continue: IDL.Record({ deleted_assets: IDL.Nat64 }),
+    Done: IDL.Record({ deleted_assets: IDL.Nat64 }),
+  });
+  const RulesType = IDL.Variant({ Db: IDL.Null, Storage: IDL.Null });
+  const StorageConfigCorsRule = IDL.Record({
+    allow_methods: IDL.Vec(IDL.Text),
+    expose_headers: IDL.Vec(IDL.Text),
+    allow_origins: IDL.Vec(IDL.Text),
+    allow_headers: IDL.Vec(IDL.Text),
+    max_age: IDL.Opt(IDL.Nat32),
+  });
+  const StorageConfigRedirect = IDL.Record({
+    status_code: IDL.Nat16,
+    location: IDL.Text,
+  });
+  const StorageConfig = IDL.Record({
+    cors: IDL.Vec(IDL.Tuple(IDL.Text, StorageConfigCorsRule)),
+    rewrites: IDL.Vec(IDL.Tuple(IDL.Text, IDL.Text)),
+    headers: IDL.Vec(
+      IDL.Tuple(IDL.Text, IDL.Vec(IDL.Tuple(IDL.Text, IDL.Text))),
+    ),
+    error_pages: IDL.Vec(IDL.Tuple(IDL.Nat16, IDL.Text)),
+    redirects: IDL.Vec(IDL.Tuple(IDL.Text, StorageConfigRedirect)),
+  });
+  const Config = IDL.Record({ storage: StorageConfig });
+  const WritePolicy = IDL.Variant({
+    Controllers: IDL.Null,
+    Authenticated: IDL.Null,
+    Owner: IDL.Null,
+  });
   const HttpRequest = IDL.Record({
     url: IDL.Text,
     method: IDL.Text,
     body: IDL.Vec(IDL.Nat8),
     headers: IDL.Vec(IDL.Tuple(IDL.Text, IDL.Text)),
+    certificate_version: IDL.Opt(IDL.Nat16),
   });
   const StreamingCallbackToken = IDL.Record({
     token: IDL.Opt(IDL.Text),
@@ -28,6 +70,7 @@ export const idlFactory = ({ IDL }) => {
   const HttpResponse = IDL.Record({
     body: IDL.Vec(IDL.Nat8),
     headers: IDL.Vec(IDL.Tuple(IDL.Text, IDL.Text)),
+    upgrade: IDL.Opt(IDL.Bool),
     streaming_strategy: IDL.Opt(StreamingStrategy),
     status_code: IDL.Nat16,
   });
@@ -44,6 +87,82 @@ export const idlFactory = ({ IDL }) => {
     full_path: IDL.Text,
   });
   const InitUploadResult = IDL.Record({ batch_id: IDL.Nat });
+  const Admin = IDL.Record({ created_at: IDL.Nat64 });
+  const ListOrderField = IDL.Variant({
+    UpdatedAt: IDL.Null,
+    Keys: IDL.Null,
+    CreatedAt: IDL.Null,
+  });
+  const ListOrder = IDL.Record({ field: ListOrderField, desc: IDL.Bool });
+  const ListMatcher = IDL.Record({
+    key: IDL.Opt(IDL.Text),
+    description: IDL.Opt(IDL.Text),
+  });
+  const ListPaginate = IDL.Record({
+    start_after: IDL.Opt(IDL.Text),
+    limit: IDL.Opt(IDL.Nat64),
+  });
+  const ListParams = IDL.Record({
+    order: IDL.Opt(ListOrder),
+    owner: IDL.Opt(IDL.Principal),
+    matcher: IDL.Opt(ListMatcher),
+    paginate: IDL.Opt(ListPaginate),
+  });
+  const AssetKey = IDL.Record({
+    token: IDL.Opt(IDL.Text),
+    collection: IDL.Text,
+    owner: IDL.Principal,
+    name: IDL.Text,
+    description: IDL.Opt(IDL.Text),
+    full_path: IDL.Text,
+  });
+  const AssetEncodingNoContent = IDL.Record({
+    modified: IDL.Nat64,
+    sha256: IDL.Vec(IDL.Nat8),
+    total_length: IDL.Nat,
+  });
+  const AssetNoContent = IDL.Record({
+    key: AssetKey,
+    updated_at: IDL.Nat64,
+    encodings: IDL.Vec(IDL.Tuple(IDL.Text, AssetEncodingNoContent)),
+    headers: IDL.Vec(IDL.Tuple(IDL.Text, IDL.Text)),
+    created_at: IDL.Nat64,
+  });
+  const ListResults = IDL.Record({
+    matches_pages: IDL.Opt(IDL.Nat64),
+    matches_length: IDL.Nat64,
+    items_page: IDL.Opt(IDL.Nat64),
+    items: IDL.Vec(IDL.Tuple(IDL.Text, AssetNoContent)),
+    items_length: IDL.Nat64,
+  });
+  const Permission = IDL.Variant({
+    Controllers: IDL.Null,
+    Private: IDL.Null,
+    Public: IDL.Null,
+    Managed: IDL.Null,
+  });
+  const Rule = IDL.Record({
+    updated_at: IDL.Nat64,
+    max_size: IDL.Opt(IDL.Nat),
+    read: Permission,
+    created_at: IDL.Nat64,
+    max_items: IDL.Opt(IDL.Nat64),
+    write: Permission,
+  });
+  const Result_1 = IDL.Variant({
+    Ok: IDL.Vec(IDL.Tuple(IDL.Text, Rule)),
+    Err: IDL.Text,
+  });
+  const MigrateAssetsResult = IDL.Variant({
+    Continue: IDL.Record({ migrated_assets: IDL.Nat64 }),
+    Done: IDL.Record({ migrated_assets: IDL.Nat64 }),
+  });
+  const SetRule = IDL.Record({
+    max_size: IDL.Opt(IDL.Nat),
+    read: Permission,
+    max_items: IDL.Opt(IDL.Nat64),
+    write: Permission,
+  });
   const UploadChunk = IDL.Record({
     content: IDL.Vec(IDL.Nat8),
     batch_id: IDL.Nat,
@@ -51,19 +170,44 @@ export const idlFactory = ({ IDL }) => {
   });
   const UploadChunkResult = IDL.Record({ chunk_id: IDL.Nat });
   return IDL.Service({
-    commit_asset_upload: IDL.Func([CommitBatch], [], []),
+    add_admin: IDL.Func([IDL.Principal], [Result], []),
+    commit_asset_upload: IDL.Func([CommitBatch], [CommitBatchResult], []),
+    del_admin: IDL.Func([IDL.Principal], [], []),
+    del_asset: IDL.Func([IDL.Text, IDL.Text], [], []),
+    del_assets: IDL.Func([IDL.Text], [DeleteAssetsResult], []),
+    del_assets_with_prefix: IDL.Func(
+      [IDL.Text, IDL.Text],
+      [DeleteAssetsResult],
+      [],
+    ),
+    del_rule: IDL.Func([RulesType, IDL.Text], [Result], []),
+    get_config: IDL.Func([], [Config], ["query"]),
+    get_write_policy: IDL.Func([], [WritePolicy], ["query"]),
     http_request: IDL.Func([HttpRequest], [HttpResponse], ["query"]),
     http_request_streaming_callback: IDL.Func(
       [StreamingCallbackToken],
       [StreamingCallbackHttpResponse],
       ["query"],
     ),
+    http_request_update: IDL.Func([HttpRequest], [HttpResponse], []),
     init_asset_upload: IDL.Func([InitAssetKey], [InitUploadResult], []),
+    list_admins: IDL.Func(
+      [],
+      [IDL.Vec(IDL.Tuple(IDL.Principal, Admin))],
+      ["query"],
+    ),
+    list_assets: IDL.Func([IDL.Text, ListParams], [ListResults], ["query"]),
+    list_rules: IDL.Func([RulesType], [Result_1], ["query"]),
+    migrate_assets: IDL.Func([], [MigrateAssetsResult], []),
+    set_config: IDL.Func([Config], [Result], []),
+    set_rule: IDL.Func([RulesType, IDL.Text, SetRule], [Result], []),
+    set_write_policy: IDL.Func([WritePolicy], [], []),
     upload_asset_chunk: IDL.Func([UploadChunk], [UploadChunkResult], []),
     version: IDL.Func([], [IDL.Text], ["query"]),
   });
 };
 // @ts-ignore
 export const init = ({ IDL }) => {
-  return [];
+  const InitArgs = IDL.Record({ admins: IDL.Vec(IDL.Principal) });
+  return [IDL.Opt(InitArgs)];
 };
@@ -0,0 +1,2 @@
+// Versions of the format of the values saved in stable memory
+pub const ADMIN_VERSION: u8 = 1;
@@ -0,0 +1,56 @@
+use crate::admins::store::is_admin;
+use crate::msg::ERROR_CALLER_NOT_ADMIN;
+use crate::shared::api::caller;
+
+pub fn caller_is_admin() -> Result<(), String> {
+    if is_admin(caller()) {
+        return Ok(());
+    }
+
+    Err(ERROR_CALLER_NOT_ADMIN.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::admins::store::{add_admin, del_admin, init_admins};
+    use crate::shared::api::mock::{set_caller, set_controllers};
+    use candid::Principal;
+
+    #[test]
+    fn accepts_only_the_admins() {
+        let admin = Principal::from_slice(&[1]);
+
+        set_caller(admin);
+
+        assert_eq!(caller_is_admin(), Err(ERROR_CALLER_NOT_ADMIN.to_string()));
+
+        add_admin(&admin).unwrap();
+
+        assert_eq!(caller_is_admin(), Ok(()));
+
+        del_admin(&admin);
+
+        assert_eq!(caller_is_admin(), Err(ERROR_CALLER_NOT_ADMIN.to_string()));
+    }
+
+    #[test]
+    fn accepts_the_controllers() {
+        let controller = Principal::from_slice(&[1]);
+
+        set_controllers(&[controller]);
+        set_caller(controller);
+
+        assert_eq!(caller_is_admin(), Ok(()));
+    }
+
+    #[test]
+    fn rejects_the_anonymous_principal() {
+        init_admins(&[Principal::anonymous()]);
+
+        set_caller(Principal::anonymous());
+
+        assert_eq!(caller_is_admin(), Err(ERROR_CALLER_NOT_ADMIN.to_string()));
+        assert!(add_admin(&Principal::anonymous()).is_err());
+    }
+}
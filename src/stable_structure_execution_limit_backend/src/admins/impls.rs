@@ -0,0 +1,48 @@
+use crate::admins::constants::ADMIN_VERSION;
+use crate::admins::types::admins::Admin;
+use crate::admins::types::state::StableAdminKey;
+use crate::shared::serializers::{deserialize_payload, read_version, serialize_versioned_to_bytes};
+use candid::Principal;
+use ic_cdk::api::trap;
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::Storable;
+use std::borrow::Cow;
+
+// The admin keys are not versioned, these are the bytes of the principals
+impl Storable for StableAdminKey {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(self.principal.as_slice().to_vec())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        StableAdminKey {
+            principal: Principal::from_slice(&bytes),
+        }
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: 29,
+        is_fixed_size: false,
+    };
+}
+
+impl Storable for Admin {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        serialize_versioned_to_bytes(ADMIN_VERSION, self)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_admin(&bytes).unwrap_or_else(|err| trap(&err))
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Decoders
+
+fn decode_admin(bytes: &[u8]) -> Result<Admin, String> {
+    match read_version(bytes) {
+        (Some(ADMIN_VERSION), payload) => deserialize_payload(payload),
+        (version, _) => Err(format!("Unsupported admin version {:?}.", version)),
+    }
+}
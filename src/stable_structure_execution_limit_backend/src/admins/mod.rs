@@ -0,0 +1,6 @@
+mod constants;
+pub mod guards;
+pub mod impls;
+mod state;
+pub mod store;
+pub mod types;
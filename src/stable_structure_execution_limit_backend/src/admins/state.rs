@@ -0,0 +1,45 @@
+use crate::admins::types::admins::Admin;
+use crate::admins::types::state::{AdminsStable, StableAdminKey};
+use crate::memory::STATE;
+use candid::Principal;
+
+pub fn is_admin(principal: &Principal) -> bool {
+    STATE.with(|state| is_admin_stable(principal, &state.borrow().stable.admins))
+}
+
+pub fn get_admins() -> Vec<(Principal, Admin)> {
+    STATE.with(|state| get_admins_stable(&state.borrow().stable.admins))
+}
+
+pub fn insert_admin(principal: &Principal, admin: &Admin) {
+    STATE.with(|state| insert_admin_stable(principal, admin, &mut state.borrow_mut().stable.admins))
+}
+
+pub fn delete_admin(principal: &Principal) {
+    STATE.with(|state| delete_admin_stable(principal, &mut state.borrow_mut().stable.admins))
+}
+
+fn is_admin_stable(principal: &Principal, admins: &AdminsStable) -> bool {
+    admins.contains_key(&stable_admin_key(principal))
+}
+
+fn get_admins_stable(admins: &AdminsStable) -> Vec<(Principal, Admin)> {
+    admins
+        .iter()
+        .map(|(key, admin)| (key.principal, admin))
+        .collect()
+}
+
+fn insert_admin_stable(principal: &Principal, admin: &Admin, admins: &mut AdminsStable) {
+    admins.insert(stable_admin_key(principal), admin.clone());
+}
+
+fn delete_admin_stable(principal: &Principal, admins: &mut AdminsStable) {
+    admins.remove(&stable_admin_key(principal));
+}
+
+fn stable_admin_key(principal: &Principal) -> StableAdminKey {
+    StableAdminKey {
+        principal: *principal,
+    }
+}
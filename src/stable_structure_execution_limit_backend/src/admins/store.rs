@@ -0,0 +1,40 @@
+use crate::admins::state::{
+    delete_admin as delete_state_admin, get_admins as get_state_admins,
+    insert_admin as insert_state_admin, is_admin as is_state_admin,
+};
+use crate::admins::types::admins::Admin;
+use crate::msg::ERROR_ANONYMOUS_ADMIN;
+use crate::shared::api::{is_controller, time};
+use candid::Principal;
+
+// The controllers of the canister are always admins, whether or not these are part of the admin set
+pub fn is_admin(principal: Principal) -> bool {
+    is_controller(&principal) || is_state_admin(&principal)
+}
+
+pub fn list_admins() -> Vec<(Principal, Admin)> {
+    get_state_admins()
+}
+
+pub fn add_admin(principal: &Principal) -> Result<(), String> {
+    if principal == &Principal::anonymous() {
+        return Err(ERROR_ANONYMOUS_ADMIN.to_string());
+    }
+
+    if !is_state_admin(principal) {
+        insert_state_admin(principal, &Admin { created_at: time() });
+    }
+
+    Ok(())
+}
+
+pub fn del_admin(principal: &Principal) {
+    delete_state_admin(principal);
+}
+
+pub fn init_admins(admins: &[Principal]) {
+    for admin in admins.iter() {
+        // The anonymous principal is not granted admin
+        let _ = add_admin(admin);
+    }
+}
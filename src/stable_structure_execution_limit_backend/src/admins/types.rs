@@ -0,0 +1,33 @@
+pub mod state {
+    use crate::admins::types::admins::Admin;
+    use crate::types::memory::Memory;
+    use candid::Principal;
+    use ic_stable_structures::StableBTreeMap;
+
+    pub type AdminsStable = StableBTreeMap<StableAdminKey, Admin, Memory>;
+
+    #[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+    pub struct StableAdminKey {
+        pub principal: Principal,
+    }
+}
+
+pub mod admins {
+    use candid::CandidType;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(CandidType, Serialize, Deserialize, Clone)]
+    pub struct Admin {
+        pub created_at: u64,
+    }
+}
+
+pub mod interface {
+    use candid::{CandidType, Deserialize, Principal};
+
+    #[derive(CandidType, Deserialize)]
+    pub struct InitArgs {
+        // The principals granted admin at install, in addition to the caller that installs the canister
+        pub admins: Vec<Principal>,
+    }
+}
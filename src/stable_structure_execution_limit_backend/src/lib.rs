@@ -1,42 +1,91 @@
+mod admins;
 mod impls;
 mod memory;
 mod msg;
+mod rules;
 mod shared;
 mod storage;
 mod types;
 
+use crate::admins::guards::caller_is_admin;
+use crate::admins::store::{
+    add_admin as add_admin_store, del_admin as del_admin_store, init_admins,
+    list_admins as list_admins_store,
+};
+use crate::admins::types::admins::Admin;
+use crate::admins::types::interface::InitArgs;
 use crate::memory::{init_stable_state, STATE};
+use crate::rules::store::{
+    del_rule as del_rule_store, init_default_rules, list_rules as list_rules_store,
+    set_rule as set_rule_store,
+};
+use crate::rules::types::interface::SetRule;
+use crate::rules::types::rules::Rule;
+use crate::storage::conditional::build_not_modified_response;
 use crate::storage::http::{
-    build_encodings, build_headers, create_token, error_response, streaming_strategy,
+    build_asset_headers, build_certified_encodings, build_cors_headers, build_encodings,
+    build_headers, content_length_header, create_token, error_response, head_response,
+    not_found_response, options_response, redirect_response, streaming_strategy,
 };
+use crate::storage::migration::migrate_stable_memory;
+use crate::storage::range::build_range_response;
 use crate::storage::store::{
-    commit_batch, create_batch, create_chunk, get_public_asset, get_public_asset_for_url,
+    commit_batch, create_batch, create_chunk, delete_asset, delete_assets,
+    get_config as get_config_store, get_content_chunk, get_error_page, get_public_asset,
+    get_public_asset_for_url, get_redirect_for_url, get_write_policy as get_write_policy_store,
+    init_certified_assets, init_config_globs, list_assets as list_assets_store,
+    migrate_assets as migrate_assets_store, set_config as set_config_store,
+    set_write_policy as set_write_policy_store,
 };
 use crate::storage::types::http::{
-    HttpRequest, HttpResponse, StreamingCallbackHttpResponse, StreamingCallbackToken,
+    HeaderField, HttpRequest, HttpResponse, StreamingCallbackHttpResponse, StreamingCallbackToken,
 };
 use crate::storage::types::http_request::PublicAsset;
 use crate::storage::types::interface::{
-    CommitBatch, InitAssetKey, InitUploadResult, UploadChunk, UploadChunkResult,
+    AssetNoContent, CommitBatch, CommitBatchResult, DeleteAssetsResult, InitAssetKey,
+    InitUploadResult, MigrateAssetsResult, UploadChunk, UploadChunkResult,
 };
-use crate::storage::types::store::Asset;
+use crate::storage::types::state::FullPath;
+use crate::storage::types::store::{Asset, AssetEncoding, WritePolicy};
+use crate::types::core::CollectionKey;
+use crate::types::interface::{Config, RulesType};
+use crate::types::list::{ListParams, ListResults};
 use crate::types::state::{RuntimeState, State};
+use candid::Principal;
 use ic_cdk::api::{caller, trap};
-use ic_cdk_macros::{export_candid, init, query, update};
+use ic_cdk_macros::{export_candid, init, post_upgrade, query, update};
 
 #[init]
-fn init() {
+fn init(args: Option<InitArgs>) {
     STATE.with(|state| {
         *state.borrow_mut() = State {
             stable: init_stable_state(),
             runtime: RuntimeState::default(),
         };
     });
+
+    // The principal that installs the canister is a controller, it is granted admin with the ones provided
+    let admins = args.map_or(vec![], |InitArgs { admins }| admins);
+    init_admins(&[vec![caller()], admins].concat());
+
+    init_default_rules();
+
+    init_certified_assets();
 }
 
-///
-/// Http
-///
+#[post_upgrade]
+fn post_upgrade() {
+    // Convert the assets saved with previous formats, the remaining ones are converted with migrate_assets
+    migrate_stable_memory();
+
+    // The compiled patterns of the config and the certified asset hashes are kept in heap memory and have to be rebuilt
+    init_config_globs();
+    init_certified_assets();
+}
+
+//
+// Http
+//
 
 #[query]
 fn http_request(
@@ -45,70 +94,232 @@ fn http_request(
         url,
         headers: req_headers,
         body: _,
+        certificate_version,
     }: HttpRequest,
 ) -> HttpResponse {
-    if method != "GET" {
-        return error_response(405, "Method Not Allowed.".to_string());
+    if method == "OPTIONS" {
+        return options_response(&url, &req_headers, certificate_version);
+    }
+
+    let head = method == "HEAD";
+
+    if method != "GET" && !head {
+        return error_page_response(405, "Method Not Allowed.".to_string(), &req_headers, false);
+    }
+
+    let HttpResponse {
+        body,
+        headers,
+        status_code,
+        streaming_strategy,
+        upgrade,
+    } = get_response(&url, &req_headers, head, certificate_version);
+
+    let response = HttpResponse {
+        body,
+        headers: [headers, build_cors_headers(&url, &req_headers)].concat(),
+        status_code,
+        streaming_strategy,
+        upgrade,
+    };
+
+    match head {
+        true => head_response(response),
+        false => response,
+    }
+}
+
+// The responses that are not certified, e.g. a range of the content, go through consensus instead
+#[update]
+fn http_request_update(request: HttpRequest) -> HttpResponse {
+    http_request(request)
+}
+
+fn get_response(
+    url: &String,
+    req_headers: &[HeaderField],
+    head: bool,
+    certificate_version: Option<u16>,
+) -> HttpResponse {
+    if let Some((requested_url, redirect)) = get_redirect_for_url(url) {
+        return redirect_response(&requested_url, &redirect, certificate_version);
     }
 
-    let result = get_public_asset_for_url(url);
+    let result = get_public_asset_for_url(url.to_string());
 
     match result {
         Ok(PublicAsset {
             asset,
             url: requested_url,
+            rewritten,
         }) => match asset {
             Some(asset) => {
-                let encodings = build_encodings(req_headers);
-
-                for encoding_type in encodings.iter() {
-                    if let Some(encoding) = asset.encodings.get(encoding_type) {
-                        let headers =
-                            build_headers(&requested_url, &asset, encoding, encoding_type);
-
-                        let Asset {
-                            key,
-                            headers: _,
-                            encodings: _,
-                            created_at: _,
-                            updated_at: _,
-                        } = &asset;
-
-                        match headers {
-                            Ok(headers) => {
-                                return HttpResponse {
-                                    body: encoding.content_chunks[0].clone(),
-                                    headers: headers.clone(),
-                                    status_code: 200,
-                                    streaming_strategy: streaming_strategy(
-                                        key,
-                                        encoding,
-                                        encoding_type,
-                                        &headers,
-                                    ),
-                                }
-                            }
-                            Err(err) => {
-                                return error_response(
-                                    405,
-                                    ["Permission denied. Invalid headers. ", err].join(""),
-                                );
-                            }
-                        }
-                    }
-                }
+                let encodings = build_certified_encodings(req_headers, &asset, certificate_version);
 
-                error_response(500, "No asset encoding found.".to_string())
+                asset_response(
+                    &asset,
+                    200,
+                    req_headers,
+                    &encodings,
+                    head,
+                    |encoding, encoding_type| {
+                        build_headers(
+                            &requested_url,
+                            &asset,
+                            encoding,
+                            encoding_type,
+                            rewritten,
+                            certificate_version,
+                        )
+                    },
+                )
+                .unwrap_or_else(|(status_code, body)| {
+                    error_page_response(status_code, body, req_headers, head)
+                })
             }
-            None => error_response(404, "No asset found.".to_string()),
+            None => not_found_page_response(&requested_url, req_headers, head, certificate_version),
         },
-        Err(err) => error_response(
+        Err(err) => error_page_response(
             405,
             ["Permission denied. Cannot perform this operation. ", err].join(""),
+            req_headers,
+            head,
         ),
     }
 }
 
+// The content of the first of the encodings the asset has - only its length for a HEAD request - or the status code and message of the error
+fn asset_response(
+    asset: &Asset,
+    status_code: u16,
+    req_headers: &[HeaderField],
+    encodings: &[String],
+    head: bool,
+    build_headers: impl Fn(&AssetEncoding, &String) -> Result<Vec<HeaderField>, &'static str>,
+) -> Result<HttpResponse, (u16, String)> {
+    for encoding_type in encodings.iter() {
+        if let Some(encoding) = asset.encodings.get(encoding_type) {
+            let headers = build_headers(encoding, encoding_type);
+
+            let Asset {
+                key,
+                headers: _,
+                encodings: _,
+                created_at: _,
+                updated_at: _,
+            } = asset;
+
+            // Only a successful response can be conditional
+            if let (Ok(headers), 200) = (&headers, status_code) {
+                if let Some(response) = build_not_modified_response(req_headers, encoding, headers)
+                {
+                    return Ok(response);
+                }
+            }
+
+            if let (Ok(headers), true) = (&headers, head) {
+                return Ok(HttpResponse {
+                    body: Vec::new(),
+                    headers: [
+                        headers.clone(),
+                        vec![content_length_header(encoding.total_length)],
+                    ]
+                    .concat(),
+                    status_code,
+                    streaming_strategy: None,
+                    upgrade: None,
+                });
+            }
+
+            // Likewise only its content can be requested partially
+            if let (Ok(headers), 200) = (&headers, status_code) {
+                if let Some(response) = build_range_response(req_headers, encoding, headers) {
+                    return Ok(response);
+                }
+            }
+
+            let body = get_content_chunk(encoding, 0);
+
+            return match (headers, body) {
+                (Ok(headers), Some(body)) => Ok(HttpResponse {
+                    body,
+                    headers: headers.clone(),
+                    status_code,
+                    streaming_strategy: streaming_strategy(key, encoding, encoding_type, &headers),
+                    upgrade: None,
+                }),
+                (Err(err), _) => Err((405, ["Permission denied. Invalid headers. ", err].join(""))),
+                (_, None) => Err((500, "No asset content found.".to_string())),
+            };
+        }
+    }
+
+    // The content is only stored in encodings the request does not accept, e.g. uploaded only with the compress encoding
+    if status_code == 200 && !asset.encodings.is_empty() {
+        return Err((406, "No acceptable asset encoding found.".to_string()));
+    }
+
+    Err((500, "No asset encoding found.".to_string()))
+}
+
+// The page of the config is certified for the wildcards, as the plain text response it replaces
+fn not_found_page_response(
+    url: &str,
+    req_headers: &[HeaderField],
+    head: bool,
+    certificate_version: Option<u16>,
+) -> HttpResponse {
+    match get_error_page(404) {
+        Some(page) => asset_response(
+            &page,
+            404,
+            req_headers,
+            &build_certified_encodings(req_headers, &page, certificate_version),
+            head,
+            |encoding, encoding_type| {
+                build_headers(
+                    url,
+                    &page,
+                    encoding,
+                    encoding_type,
+                    true,
+                    certificate_version,
+                )
+            },
+        )
+        .unwrap_or_else(|_| not_found_response(url, certificate_version)),
+        None => not_found_response(url, certificate_version),
+    }
+}
+
+// The other errors are not certified, their page is served with the headers of its own path
+fn error_page_response(
+    status_code: u16,
+    body: String,
+    req_headers: &[HeaderField],
+    head: bool,
+) -> HttpResponse {
+    match get_error_page(status_code) {
+        Some(page) => asset_response(
+            &page,
+            status_code,
+            req_headers,
+            &build_encodings(req_headers),
+            head,
+            |encoding, encoding_type| {
+                Ok(build_asset_headers(
+                    &page.key.full_path,
+                    &page,
+                    encoding,
+                    encoding_type,
+                ))
+            },
+        )
+        .unwrap_or_else(|_| error_response(status_code, body.clone())),
+        None => error_response(status_code, body),
+    }
+}
+
 #[query]
 fn http_request_streaming_callback(
     StreamingCallbackToken {
@@ -127,9 +338,12 @@ fn http_request_streaming_callback(
             let encoding = asset.encodings.get(&encoding_type);
 
             match encoding {
-                Some(encoding) => StreamingCallbackHttpResponse {
-                    token: create_token(&asset.key, index, encoding, &encoding_type, &headers),
-                    body: encoding.content_chunks[index].clone(),
+                Some(encoding) => match get_content_chunk(encoding, index) {
+                    Some(body) => StreamingCallbackHttpResponse {
+                        token: create_token(&asset.key, index, encoding, &encoding_type, &headers),
+                        body,
+                    },
+                    None => trap("Streamed asset content not found."),
                 },
                 None => trap("Streamed asset encoding not found."),
             }
@@ -166,13 +380,126 @@ fn upload_asset_chunk(chunk: UploadChunk) -> UploadChunkResult {
 }
 
 #[update]
-fn commit_asset_upload(commit: CommitBatch) {
+fn commit_asset_upload(commit: CommitBatch) -> CommitBatchResult {
     let caller = caller();
 
-    commit_batch(caller, commit).unwrap_or_else(|e| trap(&e));
+    commit_batch(caller, commit).unwrap_or_else(|e| trap(&e))
+}
+
+//
+// List
+//
+
+#[query]
+fn list_assets(collection: CollectionKey, filters: ListParams) -> ListResults<AssetNoContent> {
+    let caller = caller();
+
+    list_assets_store(caller, &collection, &filters).unwrap_or_else(|e| trap(&e))
+}
+
+//
+// Rules
+//
+
+#[update(guard = "caller_is_admin")]
+fn set_rule(rules_type: RulesType, collection: CollectionKey, rule: SetRule) -> Result<(), String> {
+    set_rule_store(&rules_type, &collection, rule)
+}
+
+#[update(guard = "caller_is_admin")]
+fn del_rule(rules_type: RulesType, collection: CollectionKey) -> Result<(), String> {
+    del_rule_store(&rules_type, &collection)
+}
+
+#[query(guard = "caller_is_admin")]
+fn list_rules(rules_type: RulesType) -> Result<Vec<(CollectionKey, Rule)>, String> {
+    list_rules_store(&rules_type)
+}
+
+//
+// Config
+//
+
+#[update(guard = "caller_is_admin")]
+fn set_config(config: Config) -> Result<(), String> {
+    set_config_store(&config.storage)
+}
+
+#[query(guard = "caller_is_admin")]
+fn get_config() -> Config {
+    Config {
+        storage: get_config_store(),
+    }
+}
+
+//
+// Write policy
+//
+
+#[update(guard = "caller_is_admin")]
+fn set_write_policy(policy: WritePolicy) {
+    set_write_policy_store(&policy);
+}
+
+#[query]
+fn get_write_policy() -> WritePolicy {
+    get_write_policy_store()
+}
+
+//
+// Delete
+//
+
+#[update]
+fn del_asset(collection: CollectionKey, full_path: FullPath) {
+    let caller = caller();
+
+    delete_asset(caller, &collection, &full_path).unwrap_or_else(|e| trap(&e));
+}
+
+#[update]
+fn del_assets(collection: CollectionKey) -> DeleteAssetsResult {
+    let caller = caller();
+
+    delete_assets(caller, &collection, &None).unwrap_or_else(|e| trap(&e))
+}
+
+#[update]
+fn del_assets_with_prefix(collection: CollectionKey, prefix: FullPath) -> DeleteAssetsResult {
+    let caller = caller();
+
+    delete_assets(caller, &collection, &Some(prefix)).unwrap_or_else(|e| trap(&e))
+}
+
+//
+// Admins
+//
+
+#[update(guard = "caller_is_admin")]
+fn add_admin(admin: Principal) -> Result<(), String> {
+    add_admin_store(&admin)
+}
+
+#[update(guard = "caller_is_admin")]
+fn del_admin(admin: Principal) {
+    del_admin_store(&admin);
+}
+
+#[query(guard = "caller_is_admin")]
+fn list_admins() -> Vec<(Principal, Admin)> {
+    list_admins_store()
+}
+
+//
+// Migration
+//
+
+#[update(guard = "caller_is_admin")]
+fn migrate_assets() -> MigrateAssetsResult {
+    migrate_assets_store()
 }
 
-/// Mgmt
+// Mgmt
 
 #[query]
 fn version() -> String {
@@ -1,11 +1,26 @@
+use crate::storage::types::config::StorageConfig;
+use crate::storage::types::state::StableUploadIds;
+use crate::storage::types::store::WritePolicy;
 use crate::types::memory::Memory;
 use crate::types::state::{StableState, State};
 use ic_stable_structures::memory_manager::{MemoryId, MemoryManager};
 use ic_stable_structures::DefaultMemoryImpl;
-use ic_stable_structures::StableBTreeMap;
+use ic_stable_structures::{StableBTreeMap, StableCell};
 use std::cell::RefCell;
 
 const ASSETS: MemoryId = MemoryId::new(2);
+const CONTENT_CHUNKS: MemoryId = MemoryId::new(3);
+const BLOBS: MemoryId = MemoryId::new(4);
+const BATCHES: MemoryId = MemoryId::new(5);
+const CHUNKS: MemoryId = MemoryId::new(6);
+const COMMITS: MemoryId = MemoryId::new(7);
+const UPLOAD_IDS: MemoryId = MemoryId::new(8);
+const WRITE_POLICY: MemoryId = MemoryId::new(9);
+const RULES: MemoryId = MemoryId::new(10);
+const ADMINS: MemoryId = MemoryId::new(11);
+const STORAGE_CONFIG: MemoryId = MemoryId::new(12);
+const COLLECTION_ASSETS: MemoryId = MemoryId::new(13);
+const COLLECTION_COUNTS: MemoryId = MemoryId::new(14);
 
 thread_local! {
     pub static STATE: RefCell<State> = RefCell::default();
@@ -14,12 +29,75 @@ thread_local! {
         RefCell::new(MemoryManager::init(DefaultMemoryImpl::default()));
 }
 
-fn get_memory_assets() -> Memory {
+pub fn get_memory_assets() -> Memory {
     MEMORY_MANAGER.with(|m| m.borrow().get(ASSETS))
 }
 
+pub fn get_memory_content_chunks() -> Memory {
+    MEMORY_MANAGER.with(|m| m.borrow().get(CONTENT_CHUNKS))
+}
+
+pub fn get_memory_blobs() -> Memory {
+    MEMORY_MANAGER.with(|m| m.borrow().get(BLOBS))
+}
+
+fn get_memory_batches() -> Memory {
+    MEMORY_MANAGER.with(|m| m.borrow().get(BATCHES))
+}
+
+fn get_memory_chunks() -> Memory {
+    MEMORY_MANAGER.with(|m| m.borrow().get(CHUNKS))
+}
+
+fn get_memory_commits() -> Memory {
+    MEMORY_MANAGER.with(|m| m.borrow().get(COMMITS))
+}
+
+fn get_memory_upload_ids() -> Memory {
+    MEMORY_MANAGER.with(|m| m.borrow().get(UPLOAD_IDS))
+}
+
+fn get_memory_write_policy() -> Memory {
+    MEMORY_MANAGER.with(|m| m.borrow().get(WRITE_POLICY))
+}
+
+fn get_memory_rules() -> Memory {
+    MEMORY_MANAGER.with(|m| m.borrow().get(RULES))
+}
+
+fn get_memory_admins() -> Memory {
+    MEMORY_MANAGER.with(|m| m.borrow().get(ADMINS))
+}
+
+fn get_memory_storage_config() -> Memory {
+    MEMORY_MANAGER.with(|m| m.borrow().get(STORAGE_CONFIG))
+}
+
+fn get_memory_collection_assets() -> Memory {
+    MEMORY_MANAGER.with(|m| m.borrow().get(COLLECTION_ASSETS))
+}
+
+fn get_memory_collection_counts() -> Memory {
+    MEMORY_MANAGER.with(|m| m.borrow().get(COLLECTION_COUNTS))
+}
+
 pub fn init_stable_state() -> StableState {
     StableState {
         assets: StableBTreeMap::init(get_memory_assets()),
+        content_chunks: StableBTreeMap::init(get_memory_content_chunks()),
+        blobs: StableBTreeMap::init(get_memory_blobs()),
+        batches: StableBTreeMap::init(get_memory_batches()),
+        chunks: StableBTreeMap::init(get_memory_chunks()),
+        commits: StableBTreeMap::init(get_memory_commits()),
+        upload_ids: StableCell::init(get_memory_upload_ids(), StableUploadIds::default())
+            .expect("Failed to initialize the upload ids"),
+        write_policy: StableCell::init(get_memory_write_policy(), WritePolicy::default())
+            .expect("Failed to initialize the write policy"),
+        rules: StableBTreeMap::init(get_memory_rules()),
+        admins: StableBTreeMap::init(get_memory_admins()),
+        storage_config: StableCell::init(get_memory_storage_config(), StorageConfig::default())
+            .expect("Failed to initialize the storage config"),
+        collection_assets: StableBTreeMap::init(get_memory_collection_assets()),
+        collection_counts: StableBTreeMap::init(get_memory_collection_counts()),
     }
 }
@@ -1,2 +1,22 @@
 /// Storage
 pub const ERROR_CANNOT_COMMIT_BATCH: &str = "Cannot commit batch.";
+pub const ERROR_ASSET_NOT_FOUND: &str = "No asset found.";
+pub const ERROR_COLLECTION_MISMATCH: &str =
+    "Provided collection does not match existing collection.";
+pub const ERROR_CANNOT_DELETE_ASSET: &str = "Cannot delete asset.";
+pub const ERROR_CANNOT_OVERWRITE_ASSET: &str =
+    "Cannot overwrite asset. The caller is not allowed to replace the existing asset.";
+
+/// Rules
+pub const ERROR_COLLECTION_NOT_FOUND: &str = "Collection not found.";
+pub const ERROR_COLLECTION_NOT_EMPTY: &str =
+    "Collection is not empty. Its assets should be deleted first.";
+pub const ERROR_UNSUPPORTED_RULES_TYPE: &str = "Only storage rules are supported.";
+pub const ERROR_PERMISSION_DENIED: &str =
+    "Permission denied. The rule of the collection does not allow this operation.";
+pub const ERROR_MAX_SIZE_EXCEEDED: &str = "Asset exceeds the maximal size of the collection.";
+pub const ERROR_MAX_ITEMS_REACHED: &str = "Collection has reached its maximal number of assets.";
+
+/// Admins
+pub const ERROR_CALLER_NOT_ADMIN: &str = "Caller is not an admin.";
+pub const ERROR_ANONYMOUS_ADMIN: &str = "The anonymous principal cannot be an admin.";
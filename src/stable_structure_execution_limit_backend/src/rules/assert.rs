@@ -0,0 +1,28 @@
+use crate::admins::store::is_admin;
+use crate::rules::types::rules::Permission;
+use crate::shared::utils::{principal_not_anonymous, principal_not_equal};
+use candid::Principal;
+
+// Read or update an existing asset
+pub fn assert_rule(permission: &Permission, owner: Principal, caller: Principal) -> bool {
+    match permission {
+        Permission::Public => true,
+        Permission::Private => !principal_not_equal(owner, caller),
+        Permission::Managed => !principal_not_equal(owner, caller) || is_admin(caller),
+        Permission::Controllers => is_admin(caller),
+    }
+}
+
+// Create a new asset, the caller becomes its owner
+pub fn assert_create_rule(permission: &Permission, caller: Principal) -> bool {
+    match permission {
+        Permission::Public => true,
+        Permission::Private | Permission::Managed => principal_not_anonymous(caller),
+        Permission::Controllers => is_admin(caller),
+    }
+}
+
+// The assets served over HTTP are only those readable by anyone
+pub fn public_permission(permission: &Permission) -> bool {
+    permission == &Permission::Public
+}
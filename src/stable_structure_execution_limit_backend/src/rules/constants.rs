@@ -0,0 +1,5 @@
+// The collections registered when the canister is installed, e.g. the one the frontend uploads to
+pub const DEFAULT_COLLECTIONS: [&str; 1] = ["images"];
+
+// Versions of the format of the values saved in stable memory
+pub const RULE_VERSION: u8 = 1;
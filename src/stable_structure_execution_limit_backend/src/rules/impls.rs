@@ -0,0 +1,28 @@
+use crate::rules::constants::RULE_VERSION;
+use crate::rules::types::rules::Rule;
+use crate::shared::serializers::{deserialize_payload, read_version, serialize_versioned_to_bytes};
+use ic_cdk::api::trap;
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::Storable;
+use std::borrow::Cow;
+
+impl Storable for Rule {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        serialize_versioned_to_bytes(RULE_VERSION, self)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_rule(&bytes).unwrap_or_else(|err| trap(&err))
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Decoders
+
+fn decode_rule(bytes: &[u8]) -> Result<Rule, String> {
+    match read_version(bytes) {
+        (Some(RULE_VERSION), payload) => deserialize_payload(payload),
+        (version, _) => Err(format!("Unsupported rule version {:?}.", version)),
+    }
+}
@@ -0,0 +1,36 @@
+use crate::memory::STATE;
+use crate::rules::types::rules::Rule;
+use crate::rules::types::state::RulesStable;
+use crate::types::core::CollectionKey;
+
+pub fn get_rule(collection: &CollectionKey) -> Option<Rule> {
+    STATE.with(|state| get_rule_stable(collection, &state.borrow().stable.rules))
+}
+
+pub fn get_rules() -> Vec<(CollectionKey, Rule)> {
+    STATE.with(|state| get_rules_stable(&state.borrow().stable.rules))
+}
+
+pub fn insert_rule(collection: &CollectionKey, rule: &Rule) {
+    STATE.with(|state| insert_rule_stable(collection, rule, &mut state.borrow_mut().stable.rules))
+}
+
+pub fn delete_rule(collection: &CollectionKey) {
+    STATE.with(|state| delete_rule_stable(collection, &mut state.borrow_mut().stable.rules))
+}
+
+fn get_rule_stable(collection: &CollectionKey, rules: &RulesStable) -> Option<Rule> {
+    rules.get(collection)
+}
+
+fn get_rules_stable(rules: &RulesStable) -> Vec<(CollectionKey, Rule)> {
+    rules.iter().collect()
+}
+
+fn insert_rule_stable(collection: &CollectionKey, rule: &Rule, rules: &mut RulesStable) {
+    rules.insert(collection.clone(), rule.clone());
+}
+
+fn delete_rule_stable(collection: &CollectionKey, rules: &mut RulesStable) {
+    rules.remove(collection);
+}
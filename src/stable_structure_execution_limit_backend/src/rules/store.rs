@@ -0,0 +1,219 @@
+use crate::msg::{
+    ERROR_COLLECTION_NOT_EMPTY, ERROR_COLLECTION_NOT_FOUND, ERROR_UNSUPPORTED_RULES_TYPE,
+};
+use crate::rules::assert::public_permission;
+use crate::rules::constants::DEFAULT_COLLECTIONS;
+use crate::rules::state::{
+    delete_rule as delete_state_rule, get_rule as get_state_rule, get_rules as get_state_rules,
+    insert_rule as insert_state_rule,
+};
+use crate::rules::types::interface::SetRule;
+use crate::rules::types::rules::{Permission, Rule};
+use crate::shared::api::time;
+use crate::storage::store::{certify_collection, count_assets};
+use crate::types::core::CollectionKey;
+use crate::types::interface::RulesType;
+
+pub fn get_rule(collection: &CollectionKey) -> Result<Rule, String> {
+    get_state_rule(collection).ok_or_else(|| ERROR_COLLECTION_NOT_FOUND.to_string())
+}
+
+pub fn list_rules(rules_type: &RulesType) -> Result<Vec<(CollectionKey, Rule)>, String> {
+    assert_rules_type(rules_type)?;
+
+    Ok(get_state_rules())
+}
+
+pub fn set_rule(
+    rules_type: &RulesType,
+    collection: &CollectionKey,
+    SetRule {
+        read,
+        write,
+        max_size,
+        max_items,
+    }: SetRule,
+) -> Result<(), String> {
+    assert_rules_type(rules_type)?;
+
+    let now = time();
+
+    let current = get_state_rule(collection);
+
+    let created_at = current.as_ref().map_or(now, |rule| rule.created_at);
+
+    let public = public_permission(&read);
+
+    insert_state_rule(
+        collection,
+        &Rule {
+            read,
+            write,
+            max_size,
+            max_items,
+            created_at,
+            updated_at: now,
+        },
+    );
+
+    // The assets served over HTTP depend on the read permission of their collection. Only its assets are certified again, if they are now served or not served anymore.
+    if current.map_or(public, |rule| public_permission(&rule.read)) != public {
+        certify_collection(collection);
+    }
+
+    Ok(())
+}
+
+pub fn del_rule(rules_type: &RulesType, collection: &CollectionKey) -> Result<(), String> {
+    assert_rules_type(rules_type)?;
+
+    // The assets of a collection cannot be accessed without its rule
+    if count_assets(collection) > 0 {
+        return Err(ERROR_COLLECTION_NOT_EMPTY.to_string());
+    }
+
+    delete_state_rule(collection);
+
+    Ok(())
+}
+
+// The collections were not registered before the rules existed. The migration registers the collection of each asset with the permissions they had until then: readable and writable by anyone.
+pub fn init_collection_rule(collection: &CollectionKey) {
+    if get_state_rule(collection).is_some() {
+        return;
+    }
+
+    let now = time();
+
+    insert_state_rule(
+        collection,
+        &Rule {
+            read: Permission::Public,
+            write: Permission::Public,
+            max_size: None,
+            max_items: None,
+            created_at: now,
+            updated_at: now,
+        },
+    );
+}
+
+// The collections a new canister accepts uploads for, with the same permissions
+pub fn init_default_rules() {
+    for collection in DEFAULT_COLLECTIONS {
+        init_collection_rule(&collection.to_string());
+    }
+}
+
+fn assert_rules_type(rules_type: &RulesType) -> Result<(), String> {
+    match rules_type {
+        RulesType::Storage => Ok(()),
+        RulesType::Db => Err(ERROR_UNSUPPORTED_RULES_TYPE.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::store::{commit_batch, create_batch, create_chunk, delete_asset};
+    use crate::storage::types::interface::{CommitBatch, InitAssetKey, UploadChunk};
+    use candid::Principal;
+
+    fn user() -> Principal {
+        Principal::from_slice(&[1])
+    }
+
+    fn set_docs_rule() {
+        set_rule(
+            &RulesType::Storage,
+            &"docs".to_string(),
+            SetRule {
+                read: Permission::Managed,
+                write: Permission::Managed,
+                max_size: None,
+                max_items: None,
+            },
+        )
+        .unwrap();
+    }
+
+    fn upload_docs_asset(full_path: &str) {
+        let batch_id = create_batch(
+            user(),
+            InitAssetKey {
+                name: full_path.to_string(),
+                full_path: full_path.to_string(),
+                token: None,
+                collection: "docs".to_string(),
+                encoding_type: None,
+                description: None,
+            },
+        )
+        .unwrap();
+
+        let chunk_id = create_chunk(
+            user(),
+            UploadChunk {
+                batch_id,
+                content: full_path.as_bytes().to_vec(),
+                order_id: None,
+            },
+        )
+        .unwrap();
+
+        commit_batch(
+            user(),
+            CommitBatch {
+                batch_id,
+                headers: vec![],
+                chunk_ids: vec![chunk_id],
+                compress: None,
+            },
+        )
+        .unwrap();
+    }
+
+    fn delete_docs_asset(full_path: &str) {
+        delete_asset(user(), &"docs".to_string(), &full_path.to_string()).unwrap();
+    }
+
+    #[test]
+    fn registers_the_default_rules() {
+        init_default_rules();
+
+        let rule = get_rule(&"images".to_string()).unwrap();
+
+        assert!(rule.read == Permission::Public && rule.write == Permission::Public);
+        assert!(get_rule(&"docs".to_string()).is_err());
+    }
+
+    #[test]
+    fn deletes_only_the_rule_of_an_empty_collection() {
+        set_docs_rule();
+        upload_docs_asset("/docs/a.pdf");
+        upload_docs_asset("/docs/b.pdf");
+
+        assert_eq!(
+            del_rule(&RulesType::Storage, &"docs".to_string()),
+            Err(ERROR_COLLECTION_NOT_EMPTY.to_string())
+        );
+
+        delete_docs_asset("/docs/a.pdf");
+        delete_docs_asset("/docs/b.pdf");
+
+        assert_eq!(del_rule(&RulesType::Storage, &"docs".to_string()), Ok(()));
+        assert_eq!(
+            get_rule(&"docs".to_string()).err(),
+            Some(ERROR_COLLECTION_NOT_FOUND.to_string())
+        );
+    }
+
+    #[test]
+    fn accepts_only_the_storage_rules() {
+        assert_eq!(
+            list_rules(&RulesType::Db).err(),
+            Some(ERROR_UNSUPPORTED_RULES_TYPE.to_string())
+        );
+        assert!(del_rule(&RulesType::Db, &"images".to_string()).is_err());
+    }
+}
@@ -0,0 +1,50 @@
+pub mod state {
+    use crate::rules::types::rules::Rule;
+    use crate::types::core::CollectionKey;
+    use crate::types::memory::Memory;
+    use ic_stable_structures::StableBTreeMap;
+
+    pub type RulesStable = StableBTreeMap<CollectionKey, Rule, Memory>;
+}
+
+pub mod rules {
+    use candid::CandidType;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(CandidType, Serialize, Deserialize, Clone, PartialEq)]
+    pub enum Permission {
+        // Anyone
+        Public,
+        // The owner of the asset
+        Private,
+        // The owner of the asset and the admins
+        Managed,
+        // The admins only - the controllers of the canister are admins
+        Controllers,
+    }
+
+    #[derive(CandidType, Serialize, Deserialize, Clone)]
+    pub struct Rule {
+        pub read: Permission,
+        pub write: Permission,
+        // The maximal size in bytes of the content of an asset
+        pub max_size: Option<u128>,
+        // The maximal number of assets in the collection
+        pub max_items: Option<usize>,
+        pub created_at: u64,
+        pub updated_at: u64,
+    }
+}
+
+pub mod interface {
+    use crate::rules::types::rules::Permission;
+    use candid::{CandidType, Deserialize};
+
+    #[derive(CandidType, Deserialize)]
+    pub struct SetRule {
+        pub read: Permission,
+        pub write: Permission,
+        pub max_size: Option<u128>,
+        pub max_items: Option<usize>,
+    }
+}
@@ -0,0 +1,73 @@
+// The system API used by the stores. It is not available natively, the tests use a simulation instead.
+
+#[cfg(not(test))]
+pub use ic_cdk::api::{caller, instruction_counter, is_controller, set_certified_data, time};
+
+#[cfg(test)]
+pub use mock::{caller, instruction_counter, is_controller, set_certified_data, time};
+
+#[cfg(test)]
+pub mod mock {
+    use candid::Principal;
+    use std::cell::{Cell, RefCell};
+
+    thread_local! {
+        static INSTRUCTION_COUNTER: Cell<u64> = const { Cell::new(0) };
+        static INSTRUCTIONS_PER_READ: Cell<u64> = const { Cell::new(0) };
+        static TIME: Cell<u64> = const { Cell::new(0) };
+        static CERTIFIED_DATA: RefCell<Vec<u8>> = const { RefCell::new(vec![]) };
+        static CONTROLLERS: RefCell<Vec<Principal>> = const { RefCell::new(vec![]) };
+        static CALLER: Cell<Principal> = const { Cell::new(Principal::anonymous()) };
+    }
+
+    pub fn caller() -> Principal {
+        CALLER.with(Cell::get)
+    }
+
+    pub fn set_caller(principal: Principal) {
+        CALLER.with(|caller| caller.set(principal));
+    }
+
+    pub fn time() -> u64 {
+        TIME.with(Cell::get)
+    }
+
+    pub fn set_time(time: u64) {
+        TIME.with(|current| current.set(time));
+    }
+
+    // The counter advances each time it is read, as the call executes
+    pub fn instruction_counter() -> u64 {
+        let instructions = INSTRUCTION_COUNTER.with(Cell::get);
+
+        INSTRUCTION_COUNTER
+            .with(|counter| counter.set(instructions + INSTRUCTIONS_PER_READ.with(Cell::get)));
+
+        instructions
+    }
+
+    // The instructions executed so far by the call, e.g. to reach the threshold of a call performed over multiple calls
+    pub fn set_instruction_counter(instructions: u64) {
+        INSTRUCTION_COUNTER.with(|counter| counter.set(instructions));
+    }
+
+    pub fn set_instructions_per_read(instructions: u64) {
+        INSTRUCTIONS_PER_READ.with(|per_read| per_read.set(instructions));
+    }
+
+    pub fn set_certified_data(data: &[u8]) {
+        CERTIFIED_DATA.with(|certified_data| *certified_data.borrow_mut() = data.to_vec());
+    }
+
+    pub fn certified_data() -> Vec<u8> {
+        CERTIFIED_DATA.with(|certified_data| certified_data.borrow().clone())
+    }
+
+    pub fn is_controller(principal: &Principal) -> bool {
+        CONTROLLERS.with(|controllers| controllers.borrow().contains(principal))
+    }
+
+    pub fn set_controllers(principals: &[Principal]) {
+        CONTROLLERS.with(|controllers| *controllers.borrow_mut() = principals.to_vec());
+    }
+}
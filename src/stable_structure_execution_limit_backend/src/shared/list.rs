@@ -0,0 +1,87 @@
+use crate::types::core::{Compare, Key};
+use crate::types::list::{ListMatcher, ListOrder, ListOrderField, ListParams, ListResults};
+use regex::Regex;
+
+// The key and description of the matcher are regular expressions
+pub fn matcher_regex(
+    matcher: &Option<ListMatcher>,
+) -> Result<(Option<Regex>, Option<Regex>), String> {
+    match matcher {
+        None => Ok((None, None)),
+        Some(ListMatcher { key, description }) => Ok((regex(key)?, regex(description)?)),
+    }
+}
+
+fn regex(pattern: &Option<String>) -> Result<Option<Regex>, String> {
+    match pattern {
+        None => Ok(None),
+        Some(pattern) => Regex::new(pattern)
+            .map(Some)
+            .map_err(|err| ["Invalid matcher. ", &err.to_string()].join("")),
+    }
+}
+
+pub fn list_values<T: Compare + Clone>(
+    matches: Vec<(Key, T)>,
+    filters: &ListParams,
+) -> ListResults<T> {
+    let matches_length = matches.len();
+
+    let ordered = order_values(matches, &filters.order);
+
+    let start_at = start_at(&ordered, filters);
+    let limit = filters
+        .paginate
+        .as_ref()
+        .and_then(|paginate| paginate.limit);
+
+    let items: Vec<(Key, T)> = match limit {
+        None => ordered.into_iter().skip(start_at).collect(),
+        Some(limit) => ordered.into_iter().skip(start_at).take(limit).collect(),
+    };
+
+    let (items_page, matches_pages) = match limit {
+        None | Some(0) => (None, None),
+        Some(limit) => (Some(start_at / limit), Some(matches_length.div_ceil(limit))),
+    };
+
+    ListResults {
+        items_length: items.len(),
+        items,
+        items_page,
+        matches_length,
+        matches_pages,
+    }
+}
+
+fn order_values<T: Compare>(mut values: Vec<(Key, T)>, order: &Option<ListOrder>) -> Vec<(Key, T)> {
+    let ListOrder { desc, field } = order.clone().unwrap_or_default();
+
+    match field {
+        ListOrderField::Keys => values.sort_by(|(a, _), (b, _)| a.cmp(b)),
+        ListOrderField::CreatedAt => values.sort_by(|(_, a), (_, b)| a.cmp_created_at(b)),
+        ListOrderField::UpdatedAt => values.sort_by(|(_, a), (_, b)| a.cmp_updated_at(b)),
+    }
+
+    if desc {
+        values.reverse();
+    }
+
+    values
+}
+
+// The index following the start_after key. From the beginning if no key is provided or if it is not part of the matches.
+fn start_at<T>(values: &[(Key, T)], filters: &ListParams) -> usize {
+    let start_after = filters
+        .paginate
+        .as_ref()
+        .and_then(|paginate| paginate.start_after.as_ref());
+
+    match start_after {
+        None => 0,
+        Some(start_after) => values
+            .iter()
+            .position(|(key, _)| key == start_after)
+            .map_or(0, |index| index + 1),
+    }
+}
@@ -1,2 +1,4 @@
+pub mod api;
+pub mod list;
 pub mod serializers;
 pub mod utils;
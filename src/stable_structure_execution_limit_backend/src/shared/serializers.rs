@@ -2,12 +2,24 @@ use ciborium::{from_reader, into_writer};
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
 
-pub fn serialize_to_bytes<T: Serialize>(value: &T) -> Cow<[u8]> {
-    let mut bytes = vec![];
-    into_writer(value, &mut bytes).expect("Failed to serialize to bytes");
+// Marks the bytes of a versioned value. 0xff is the CBOR "break" stop code, it can never start an encoded item and therefore cannot be confused with the bytes written before values were versioned.
+const VERSIONED_MARKER: u8 = 0xff;
+
+/// Serialize a value prefixed with the version of its format.
+pub fn serialize_versioned_to_bytes<T: Serialize>(version: u8, value: &T) -> Cow<'_, [u8]> {
+    let mut bytes = vec![VERSIONED_MARKER, version];
+    into_writer(value, &mut bytes).expect("Failed to serialize versioned value to bytes");
     Cow::Owned(bytes)
 }
 
-pub fn deserialize_from_bytes<T: for<'a> Deserialize<'a>>(bytes: Cow<'_, [u8]>) -> T {
-    from_reader(&*bytes).expect("Failed to deserialize from bytes")
+/// The version of the format of serialized bytes and their payload. Bytes written before values were versioned have no version.
+pub fn read_version(bytes: &[u8]) -> (Option<u8>, &[u8]) {
+    match bytes {
+        [VERSIONED_MARKER, version, payload @ ..] => (Some(*version), payload),
+        _ => (None, bytes),
+    }
+}
+
+pub fn deserialize_payload<T: for<'a> Deserialize<'a>>(payload: &[u8]) -> Result<T, String> {
+    from_reader(payload).map_err(|err| format!("Failed to deserialize from bytes: {}", err))
 }
@@ -3,3 +3,7 @@ use candid::Principal;
 pub fn principal_not_equal(x: Principal, y: Principal) -> bool {
     x != y
 }
+
+pub fn principal_not_anonymous(p: Principal) -> bool {
+    principal_not_equal(p, Principal::anonymous())
+}
@@ -0,0 +1,311 @@
+use base64::encode;
+use ic_cdk::api::data_certificate;
+use ic_certified_map::Hash;
+use serde::Serialize;
+use serde_cbor::ser::Serializer;
+use sha2::{Digest, Sha256};
+
+use crate::shared::api::set_certified_data;
+use crate::storage::constants::{
+    CERTIFICATE_HEADER_EXCLUSIONS, EXPR_PATH_EXACT, EXPR_PATH_WILDCARD, LABEL_ASSETS_V2,
+    V1_FALLBACK_PATH,
+};
+use crate::storage::types::http::HeaderField;
+use crate::storage::types::state::AssetHashes;
+
+pub fn update_certified_data(asset_hashes: &AssetHashes) {
+    set_certified_data(&asset_hashes.root_hash());
+}
+
+// Headers
+
+pub fn build_asset_certificate_headers(
+    asset_hashes: &AssetHashes,
+    url: &str,
+    certificate_version: Option<u16>,
+) -> Result<Vec<HeaderField>, &'static str> {
+    if !is_certificate_v2(certificate_version) {
+        return build_asset_certificate_header_v1(asset_hashes, &[url.to_string()]);
+    }
+
+    let expr_path = exact_expr_path(url);
+
+    build_certificate_headers_v2(asset_hashes, &expr_path, std::slice::from_ref(&expr_path))
+}
+
+// The responses for the paths without asset - a rewrite or not found - are certified with the most specific wildcard of the path.
+// Response verification v1 has no wildcard, a verifier falls back to /index.html for a path that is not certified.
+pub fn build_wildcard_certificate_headers(
+    asset_hashes: &AssetHashes,
+    url: &str,
+    certificate_version: Option<u16>,
+) -> Result<Vec<HeaderField>, &'static str> {
+    if !is_certificate_v2(certificate_version) {
+        return build_asset_certificate_header_v1(
+            asset_hashes,
+            &[url.to_string(), V1_FALLBACK_PATH.to_string()],
+        );
+    }
+
+    let wildcards = wildcard_expr_paths(url);
+
+    // The fallback is the least specific wildcard and is always certified
+    let expr_path = wildcards
+        .iter()
+        .find(|expr_path| asset_hashes.contains_responses(expr_path))
+        .cloned()
+        .unwrap_or_else(fallback_expr_path);
+
+    // A wildcard is valid only if no more specific path exists. The proof contains the exact path and the wildcards from the most specific to the one certified.
+    let witness_expr_paths: Vec<Vec<String>> = std::iter::once(exact_expr_path(url))
+        .chain(
+            wildcards
+                .into_iter()
+                .take_while(|wildcard| wildcard != &expr_path),
+        )
+        .chain(std::iter::once(expr_path.clone()))
+        .collect();
+
+    build_certificate_headers_v2(asset_hashes, &expr_path, &witness_expr_paths)
+}
+
+// A response that is not the content of an asset - a redirect or the answer to OPTIONS - is certified for the exact path if it is the one of an asset, otherwise for the most specific wildcard of the path.
+// Response verification v1 certifies only the content of the assets, such a response cannot be certified with it.
+pub fn build_path_certificate_headers(
+    asset_hashes: &AssetHashes,
+    url: &str,
+    certificate_version: Option<u16>,
+) -> Result<Vec<HeaderField>, &'static str> {
+    if !is_certificate_v2(certificate_version) {
+        return Ok(vec![]);
+    }
+
+    let expr_path = exact_expr_path(url);
+
+    if asset_hashes.contains_responses(&expr_path) {
+        return build_certificate_headers_v2(
+            asset_hashes,
+            &expr_path,
+            std::slice::from_ref(&expr_path),
+        );
+    }
+
+    build_wildcard_certificate_headers(asset_hashes, url, certificate_version)
+}
+
+pub fn is_certificate_v2(certificate_version: Option<u16>) -> bool {
+    certificate_version.is_some_and(|version| version >= 2)
+}
+
+fn build_asset_certificate_header_v1(
+    asset_hashes: &AssetHashes,
+    paths: &[String],
+) -> Result<Vec<HeaderField>, &'static str> {
+    // The certificate is only available in query calls. The response of an update call - http_request_update - is certified by the consensus.
+    let Some(certificate) = data_certificate() else {
+        return Ok(vec![]);
+    };
+
+    let tree = serialize_cbor(&asset_hashes.witness(paths))?;
+
+    Ok(vec![HeaderField(
+        "ic-certificate".to_string(),
+        format!(
+            "certificate=:{}:, tree=:{}:",
+            encode(certificate),
+            encode(tree)
+        ),
+    )])
+}
+
+fn build_certificate_headers_v2(
+    asset_hashes: &AssetHashes,
+    expr_path: &[String],
+    witness_expr_paths: &[Vec<String>],
+) -> Result<Vec<HeaderField>, &'static str> {
+    let Some(certificate) = data_certificate() else {
+        return Ok(vec![]);
+    };
+
+    let tree = serialize_cbor(&asset_hashes.witness_responses(witness_expr_paths))?;
+    let expr_path = serialize_cbor(&expr_path)?;
+
+    Ok(vec![
+        HeaderField(
+            "ic-certificate".to_string(),
+            format!(
+                "certificate=:{}:, tree=:{}:, expr_path=:{}:, version=2",
+                encode(certificate),
+                encode(tree),
+                encode(expr_path)
+            ),
+        ),
+        HeaderField(
+            "ic-certificateexpression".to_string(),
+            certificate_expression(),
+        ),
+    ])
+}
+
+fn serialize_cbor<T: Serialize>(value: &T) -> Result<Vec<u8>, &'static str> {
+    let mut serializer = Serializer::new(vec![]);
+    serializer.self_describe().unwrap();
+
+    value
+        .serialize(&mut serializer)
+        .map_err(|_| "Failed to serialize a hash tree.")?;
+
+    Ok(serializer.into_inner())
+}
+
+// Expression paths
+
+// e.g. /hello/world.html -> ["http_expr", "hello", "world.html", "<$>"]
+// A path to a directory ends with an empty segment, /hello/ and /hello are different paths.
+fn url_segments(url: &str) -> Vec<String> {
+    let mut segments: Vec<String> = vec![LABEL_ASSETS_V2.to_string()];
+
+    segments.extend(
+        url.split('/')
+            .filter(|segment| !segment.is_empty())
+            .map(|segment| segment.to_string()),
+    );
+
+    if url.ends_with('/') {
+        segments.push("".to_string());
+    }
+
+    segments
+}
+
+pub fn exact_expr_path(url: &str) -> Vec<String> {
+    [url_segments(url), vec![EXPR_PATH_EXACT.to_string()]].concat()
+}
+
+// e.g. /hello/ -> ["http_expr", "hello", "", "<*>"]
+pub fn wildcard_expr_path(url: &str) -> Vec<String> {
+    [url_segments(url), vec![EXPR_PATH_WILDCARD.to_string()]].concat()
+}
+
+pub fn fallback_expr_path() -> Vec<String> {
+    wildcard_expr_path("/")
+}
+
+// The wildcards of a path from the most specific to the fallback, in the order these are looked up by a verifier - e.g. /hello/world -> /hello/world, /hello/, /hello, /
+fn wildcard_expr_paths(url: &str) -> Vec<Vec<String>> {
+    let root = url_segments("/");
+
+    let mut segments = url_segments(url);
+    let mut expr_paths = vec![];
+
+    loop {
+        expr_paths.push([segments.clone(), vec![EXPR_PATH_WILDCARD.to_string()]].concat());
+
+        if segments == root || segments.len() < root.len() {
+            break;
+        }
+
+        if segments.last().is_some_and(|segment| segment.is_empty()) {
+            segments.pop();
+        } else {
+            segments.pop();
+            segments.push("".to_string());
+        }
+    }
+
+    expr_paths
+}
+
+// Hashes
+
+// All the headers of the responses but the exclusions are certified, none of the request
+fn certificate_expression() -> String {
+    let exclusions: Vec<String> = CERTIFICATE_HEADER_EXCLUSIONS
+        .iter()
+        .map(|exclusion| format!("\"{}\"", exclusion))
+        .collect();
+
+    format!(
+        "default_certification(ValidationArgs{{certification:Certification{{no_request_certification:Empty{{}},response_certification:ResponseCertification{{response_header_exclusions:ResponseHeaderList{{headers:[{}]}}}}}}}})",
+        exclusions.join(",")
+    )
+}
+
+pub fn expression_hash() -> Hash {
+    sha256(certificate_expression().as_bytes())
+}
+
+// The representation independent hash of the headers - including the certificate expression and the status code - followed by the hash of the body
+pub fn response_hash(status_code: u16, headers: &[HeaderField], body_hash: &Hash) -> Hash {
+    let mut hashes: Vec<(Hash, Hash)> = headers
+        .iter()
+        .filter(|HeaderField(name, _)| {
+            !name.eq_ignore_ascii_case("ic-certificate")
+                && !name.eq_ignore_ascii_case("ic-certificateexpression")
+                && !CERTIFICATE_HEADER_EXCLUSIONS
+                    .iter()
+                    .any(|exclusion| name.eq_ignore_ascii_case(exclusion))
+        })
+        .map(|HeaderField(name, value)| {
+            (
+                sha256(name.to_ascii_lowercase().as_bytes()),
+                sha256(value.as_bytes()),
+            )
+        })
+        .collect();
+
+    hashes.push((
+        sha256(b"ic-certificateexpression"),
+        sha256(certificate_expression().as_bytes()),
+    ));
+    hashes.push((
+        sha256(b":ic-cert-status"),
+        sha256(&leb128(u64::from(status_code))),
+    ));
+
+    hashes.sort_unstable();
+
+    let mut headers_hasher = Sha256::new();
+
+    for (name, value) in hashes.iter() {
+        headers_hasher.update(name);
+        headers_hasher.update(value);
+    }
+
+    let headers_hash: Hash = headers_hasher.finalize().into();
+
+    sha256(&[headers_hash, *body_hash].concat())
+}
+
+pub fn sha256(bytes: &[u8]) -> Hash {
+    Sha256::digest(bytes).into()
+}
+
+fn leb128(mut value: u64) -> Vec<u8> {
+    let mut bytes: Vec<u8> = vec![];
+
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+
+        if value == 0 {
+            bytes.push(byte);
+            return bytes;
+        }
+
+        bytes.push(byte | 0x80);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn certificate_expression_excludes_the_headers() {
+        assert_eq!(
+            certificate_expression(),
+            "default_certification(ValidationArgs{certification:Certification{no_request_certification:Empty{},response_certification:ResponseCertification{response_header_exclusions:ResponseHeaderList{headers:[\"access-control-allow-origin\",\"access-control-expose-headers\",\"access-control-allow-methods\",\"access-control-allow-headers\",\"access-control-max-age\"]}}}})"
+        );
+    }
+}
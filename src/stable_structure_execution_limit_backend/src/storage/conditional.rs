@@ -0,0 +1,254 @@
+use crate::storage::http::find_header;
+use crate::storage::types::http::{HeaderField, HttpResponse};
+use crate::storage::types::store::AssetEncoding;
+
+const DAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+const SECONDS_PER_DAY: u64 = 86_400;
+
+/// Build a 304 response if the representation cached by the client is still the one of the encoding.
+/// "If-None-Match" is compared with the ETag and takes precedence, "If-Modified-Since" is only evaluated without it.
+/// The response has the headers of the full response - ETag, Last-Modified, Vary etc. - but no content. It is certified with the one of the encoding.
+pub fn build_not_modified_response(
+    req_headers: &[HeaderField],
+    encoding: &AssetEncoding,
+    headers: &[HeaderField],
+) -> Option<HttpResponse> {
+    let not_modified = match find_header(req_headers, "if-none-match") {
+        Some(if_none_match) => {
+            let etag = find_header(headers, "etag")?;
+            matches_etag(if_none_match, etag)
+        }
+        None => {
+            let since = parse_http_date(find_header(req_headers, "if-modified-since")?)?;
+            encoding.modified / NANOS_PER_SECOND <= since
+        }
+    };
+
+    if !not_modified {
+        return None;
+    }
+
+    Some(HttpResponse {
+        body: Vec::new(),
+        headers: headers.to_owned(),
+        status_code: 304,
+        streaming_strategy: None,
+        upgrade: None,
+    })
+}
+
+/// Whether the ranges of a request can be served: "If-Range" is missing or its validator is the one of the encoding.
+/// An entity tag has to match strongly - a weak one never does - and a date has to be the one of Last-Modified. Otherwise the full content is served.
+pub fn matches_if_range(
+    req_headers: &[HeaderField],
+    encoding: &AssetEncoding,
+    headers: &[HeaderField],
+) -> bool {
+    let Some(if_range) = find_header(req_headers, "if-range") else {
+        return true;
+    };
+
+    let if_range = if_range.trim();
+
+    if if_range.starts_with('"') || if_range.starts_with("W/") {
+        return find_header(headers, "etag")
+            .is_some_and(|etag| matches_strong_etag(if_range, etag));
+    }
+
+    parse_http_date(if_range).is_some_and(|date| encoding.modified / NANOS_PER_SECOND == date)
+}
+
+// The strong comparison of RFC 9110 - both entity tags are strong and identical
+fn matches_strong_etag(tag: &str, etag: &str) -> bool {
+    let etag = etag.trim();
+
+    !tag.starts_with("W/") && !etag.starts_with("W/") && tag == etag
+}
+
+// The weak comparison of RFC 9110 - e.g. If-None-Match: "abc", W/"def" or *
+fn matches_etag(if_none_match: &str, etag: &str) -> bool {
+    let opaque_tag = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+
+    let etag = opaque_tag(etag);
+
+    if_none_match
+        .split(',')
+        .any(|tag| tag.trim() == "*" || opaque_tag(tag) == etag)
+}
+
+// Dates
+
+// The IMF-fixdate of a timestamp in nanoseconds - e.g. Sun, 06 Nov 1994 08:49:37 GMT
+pub fn http_date(nanos: u64) -> String {
+    let seconds = nanos / NANOS_PER_SECOND;
+    let days = seconds / SECONDS_PER_DAY;
+    let time = seconds % SECONDS_PER_DAY;
+
+    let (year, month, day) = civil_from_days(days);
+
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        // 1 January 1970 was a Thursday
+        DAYS[((days + 4) % 7) as usize],
+        day,
+        MONTHS[(month - 1) as usize],
+        year,
+        time / 3600,
+        (time % 3600) / 60,
+        time % 60
+    )
+}
+
+// The seconds since the epoch of an IMF-fixdate. The obsolete formats are not supported, a request with such a date is served as if the header were not provided.
+fn parse_http_date(date: &str) -> Option<u64> {
+    let (_, date) = date.trim().split_once(", ")?;
+
+    let parts: Vec<&str> = date.split(' ').collect();
+
+    let [day, month, year, time, "GMT"] = parts.as_slice() else {
+        return None;
+    };
+
+    let day: u64 = day.parse().ok()?;
+    let month = MONTHS.iter().position(|name| name == month)? as u64 + 1;
+    let year: u64 = year.parse().ok()?;
+
+    let time: Vec<u64> = time
+        .split(':')
+        .map(|value| value.parse().ok())
+        .collect::<Option<Vec<u64>>>()?;
+
+    let [hours, minutes, seconds] = time.as_slice() else {
+        return None;
+    };
+
+    if year < 1970 || !(1..=31).contains(&day) || *hours > 23 || *minutes > 59 || *seconds > 60 {
+        return None;
+    }
+
+    let days = days_from_civil(year, month, day);
+
+    Some(days * SECONDS_PER_DAY + hours * 3600 + minutes * 60 + seconds)
+}
+
+// Source: http://howardhinnant.github.io/date_algorithms.html - limited to the dates since the epoch
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+
+    (year, month, day)
+}
+
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let yoe = year - era * 400;
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+
+    era * 146_097 + doe - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_strong_and_weak_etags() {
+        assert!(matches_etag("\"abc\"", "\"abc\""));
+        assert!(matches_etag("W/\"abc\"", "\"abc\""));
+        assert!(matches_etag("\"abc\"", "W/\"abc\""));
+        assert!(matches_etag("\"def\", W/\"abc\"", "\"abc\""));
+        assert!(!matches_etag("\"def\"", "\"abc\""));
+        assert!(!matches_etag("\"abc\"", "\"abcd\""));
+    }
+
+    #[test]
+    fn matches_any_etag() {
+        assert!(matches_etag("*", "\"abc\""));
+        assert!(matches_etag(" * ", "\"abc\""));
+    }
+
+    fn encoding(modified: u64) -> AssetEncoding {
+        AssetEncoding {
+            modified,
+            content_chunks: vec![],
+            content_chunk_lengths: vec![],
+            total_length: 0,
+            sha256: [0; 32],
+            decompressed: false,
+        }
+    }
+
+    fn if_range(value: &str, modified: u64) -> bool {
+        matches_if_range(
+            &[HeaderField("if-range".to_string(), value.to_string())],
+            &encoding(modified),
+            &[HeaderField("etag".to_string(), "\"abc\"".to_string())],
+        )
+    }
+
+    #[test]
+    fn matches_if_range_etag_strongly() {
+        assert!(if_range("\"abc\"", 0));
+        assert!(!if_range("W/\"abc\"", 0));
+        assert!(!if_range("\"def\"", 0));
+    }
+
+    #[test]
+    fn matches_if_range_last_modified_exactly() {
+        let modified = 784_111_777 * NANOS_PER_SECOND;
+
+        assert!(if_range("Sun, 06 Nov 1994 08:49:37 GMT", modified));
+        assert!(if_range("Sun, 06 Nov 1994 08:49:37 GMT", modified + 1));
+        assert!(!if_range("Sun, 06 Nov 1994 08:49:38 GMT", modified));
+        assert!(!if_range("Sun, 06 Nov 1994 08:49:36 GMT", modified));
+        assert!(!if_range("Sunday, 06-Nov-94 08:49:37 GMT", modified));
+    }
+
+    #[test]
+    fn serves_ranges_without_if_range() {
+        assert!(matches_if_range(&[], &encoding(0), &[]));
+    }
+
+    #[test]
+    fn parses_imf_fixdate() {
+        assert_eq!(
+            parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"),
+            Some(784_111_777)
+        );
+        assert_eq!(parse_http_date("Thu, 01 Jan 1970 00:00:00 GMT"), Some(0));
+        assert_eq!(
+            parse_http_date(&http_date(1_700_000_000 * NANOS_PER_SECOND)),
+            Some(1_700_000_000)
+        );
+    }
+
+    #[test]
+    fn ignores_malformed_dates() {
+        // Obsolete formats
+        assert_eq!(parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"), None);
+        assert_eq!(parse_http_date("Sun Nov  6 08:49:37 1994"), None);
+
+        assert_eq!(parse_http_date(""), None);
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 UTC"), None);
+        assert_eq!(parse_http_date("Sun, 06 Foo 1994 08:49:37 GMT"), None);
+        assert_eq!(parse_http_date("Sun, 32 Nov 1994 08:49:37 GMT"), None);
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 24:00:00 GMT"), None);
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49 GMT"), None);
+        assert_eq!(parse_http_date("Wed, 31 Dec 1969 23:59:59 GMT"), None);
+    }
+}
@@ -6,3 +6,70 @@ pub static ENCODING_CERTIFICATION_ORDER: &[&str] = &[
     "deflate",
     "br",
 ];
+
+// The encodings served from the smallest to the largest content, when a request accepts several equally
+pub static ENCODING_PREFERENCE_ORDER: &[&str] = &[
+    "br",
+    "gzip",
+    "deflate",
+    "compress",
+    ASSET_ENCODING_NO_COMPRESSION,
+];
+
+// The encodings a verifier of response verification v1 decodes before it hashes the body, as it certifies the sha256 of the identity encoding
+pub static CERTIFICATE_V1_ENCODINGS: &[&str] = &[ASSET_ENCODING_NO_COMPRESSION, "gzip", "deflate"];
+
+// The encodings derived from the identity encoding when a commit requests the compression
+pub static COMPRESSION_ENCODINGS: &[&str] = &["br", "gzip"];
+
+// The trade-off between the size of the content and the instructions used by brotli - a quality up to 11 and a window of 2^22 bytes
+pub const BROTLI_QUALITY: u32 = 5;
+pub const BROTLI_WINDOW: u32 = 22;
+
+// The encodings an identity encoding is decompressed from when an asset is uploaded without it, for the clients that accept none of the compressed encodings
+pub static DECOMPRESSION_ENCODINGS: &[&str] = &["gzip", "deflate", "br"];
+
+// A small compressed content can be decompressed in a very large one, the length of a decompressed identity encoding is limited
+pub const MAX_DECOMPRESSED_LENGTH: u128 = 50_000_000;
+
+// The compressed content is written in chunks that can be served in a single response
+pub const COMPRESSED_CHUNK_LENGTH: usize = 1_900_000;
+
+// The labels of the trees of asset hashes in the certified data, as expected by the boundary nodes for response verification v1 and v2
+pub const LABEL_ASSETS_V1: &str = "http_assets";
+pub const LABEL_ASSETS_V2: &str = "http_expr";
+
+// Terminate the path of a certification expression matching exactly the requested path or any path with the prefix
+pub const EXPR_PATH_EXACT: &str = "<$>";
+pub const EXPR_PATH_WILDCARD: &str = "<*>";
+
+// The path a verifier of response verification v1 falls back to when the requested path is not certified
+pub const V1_FALLBACK_PATH: &str = "/index.html";
+
+// The status codes a redirect of the config can be served with
+pub const REDIRECT_STATUS_CODES: [u16; 4] = [301, 302, 307, 308];
+
+// The methods answered by http_request
+pub const HTTP_METHODS: [&str; 3] = ["GET", "HEAD", "OPTIONS"];
+
+// The headers of the responses that depend on the origin of the request - or on the preflight request - cannot be certified
+pub const CERTIFICATE_HEADER_EXCLUSIONS: [&str; 5] = [
+    "access-control-allow-origin",
+    "access-control-expose-headers",
+    "access-control-allow-methods",
+    "access-control-allow-headers",
+    "access-control-max-age",
+];
+
+// Versions of the format of the values saved in stable memory
+pub const ASSET_VERSION: u8 = 1;
+pub const FULL_PATH_VERSION: u8 = 1;
+pub const COLLECTION_ASSET_KEY_VERSION: u8 = 1;
+pub const CHUNK_KEY_VERSION: u8 = 1;
+pub const BLOB_VERSION: u8 = 1;
+pub const BATCH_VERSION: u8 = 1;
+pub const CHUNK_VERSION: u8 = 1;
+pub const COMMIT_VERSION: u8 = 1;
+pub const UPLOAD_IDS_VERSION: u8 = 1;
+pub const WRITE_POLICY_VERSION: u8 = 1;
+pub const STORAGE_CONFIG_VERSION: u8 = 1;
@@ -2,11 +2,24 @@ use hex::encode;
 use ic_cdk::id;
 use serde_bytes::ByteBuf;
 
-use crate::storage::constants::ASSET_ENCODING_NO_COMPRESSION;
+use crate::msg::ERROR_ASSET_NOT_FOUND;
+use crate::storage::cert::is_certificate_v2;
+use crate::storage::conditional::http_date;
+use crate::storage::constants::{
+    ASSET_ENCODING_NO_COMPRESSION, CERTIFICATE_V1_ENCODINGS, ENCODING_PREFERENCE_ORDER,
+    HTTP_METHODS,
+};
+use crate::storage::runtime::{
+    build_certified_asset_headers, build_certified_path_headers, build_certified_wildcard_headers,
+    get_config_cors, get_config_headers,
+};
+use crate::storage::types::config::{StorageConfigCorsRule, StorageConfigRedirect};
 use crate::storage::types::http::{
     CallbackFunc, HeaderField, HttpResponse, StreamingCallbackToken, StreamingStrategy,
 };
+use crate::storage::types::http_request::MapUrl;
 use crate::storage::types::store::{Asset, AssetEncoding, AssetKey};
+use crate::storage::url::map_url;
 
 pub fn streaming_strategy(
     key: &AssetKey,
@@ -45,12 +58,42 @@ pub fn create_token(
 }
 
 pub fn build_headers(
-    _url: &str,
+    url: &str,
     asset: &Asset,
     encoding: &AssetEncoding,
     encoding_type: &String,
+    rewritten: bool,
+    certificate_version: Option<u16>,
 ) -> Result<Vec<HeaderField>, &'static str> {
-    let mut headers = asset.headers.clone();
+    // A rewritten asset is certified for the wildcard of the rewrite, its response has to be the same for all the paths it is served for. Therefore its headers are the ones of its own path.
+    if rewritten {
+        let certificate_headers = build_certified_wildcard_headers(url, certificate_version)?;
+
+        return Ok([
+            build_asset_headers(&asset.key.full_path, asset, encoding, encoding_type),
+            certificate_headers,
+        ]
+        .concat());
+    }
+
+    let certificate_headers = build_certified_asset_headers(url, certificate_version)?;
+
+    Ok([
+        build_asset_headers(url, asset, encoding, encoding_type),
+        certificate_headers,
+    ]
+    .concat())
+}
+
+// The headers of an asset served for a path, without the certificate. These are certified with response verification v2.
+// A header overrides the one with the same name of lower precedence: the security headers, then the headers of the asset, then the headers of the config matching the path. The headers describing the content cannot be overridden.
+pub fn build_asset_headers(
+    path: &str,
+    asset: &Asset,
+    encoding: &AssetEncoding,
+    encoding_type: &String,
+) -> Vec<HeaderField> {
+    let mut headers: Vec<HeaderField> = vec![];
 
     // The Accept-Ranges HTTP response header is a marker used by the server to advertise its support for partial requests from the client for file downloads.
     headers.push(HeaderField(
@@ -63,14 +106,220 @@ pub fn build_headers(
         format!("\"{}\"", encode(encoding.sha256)),
     ));
 
-    if encoding_type.clone() != *ASSET_ENCODING_NO_COMPRESSION {
+    headers.push(HeaderField(
+        "last-modified".to_string(),
+        http_date(encoding.modified),
+    ));
+
+    // The content served for the path depends on the encodings accepted by the request, and its CORS headers on the origin
+    headers.push(HeaderField(
+        "vary".to_string(),
+        match get_config_cors(path) {
+            None => "accept-encoding".to_string(),
+            Some(_) => "accept-encoding, origin".to_string(),
+        },
+    ));
+
+    if encoding_type != ASSET_ENCODING_NO_COMPRESSION {
         headers.push(HeaderField(
-            "Content-Encoding".to_string(),
+            "content-encoding".to_string(),
             encoding_type.to_string(),
         ));
     }
 
-    Ok([headers, security_headers()].concat())
+    merge_headers(&[
+        security_headers(),
+        asset.headers.clone(),
+        get_config_headers(path),
+        headers,
+    ])
+}
+
+// A response to a HEAD request has the headers of the response to GET and the length of its content, but no content. A not modified response has no content to measure.
+// The response of an asset served in the update call has no length yet, the request is upgraded as for GET.
+pub fn head_response(
+    HttpResponse {
+        body,
+        headers,
+        status_code,
+        streaming_strategy,
+        upgrade,
+    }: HttpResponse,
+) -> HttpResponse {
+    if upgrade == Some(true) {
+        return HttpResponse {
+            body,
+            headers,
+            status_code,
+            streaming_strategy,
+            upgrade,
+        };
+    }
+
+    let content_length = match (find_header(&headers, "content-length"), status_code) {
+        (Some(_), _) | (_, 304) => vec![],
+        (None, _) => vec![content_length_header(body.len() as u128)],
+    };
+
+    HttpResponse {
+        body: Vec::new(),
+        headers: [headers, content_length].concat(),
+        status_code,
+        streaming_strategy: None,
+        upgrade: None,
+    }
+}
+
+pub fn content_length_header(length: u128) -> HeaderField {
+    HeaderField("content-length".to_string(), length.to_string())
+}
+
+// CORS
+
+// The headers allowing the origin of the request to read the response. These depend on the request and are therefore excluded from the certification.
+pub fn build_cors_headers(url: &str, req_headers: &[HeaderField]) -> Vec<HeaderField> {
+    let Some(cors) = get_config_cors(&url_path(url)) else {
+        return vec![];
+    };
+
+    let Some(origin) = allowed_origin(&cors, req_headers) else {
+        return vec![];
+    };
+
+    let mut headers = vec![HeaderField(
+        "access-control-allow-origin".to_string(),
+        origin,
+    )];
+
+    if !cors.expose_headers.is_empty() {
+        headers.push(HeaderField(
+            "access-control-expose-headers".to_string(),
+            cors.expose_headers.join(", "),
+        ));
+    }
+
+    headers
+}
+
+// The answer to a preflight request - or the methods allowed if the request is not a preflight or its origin is not allowed
+pub fn options_response(
+    url: &str,
+    req_headers: &[HeaderField],
+    certificate_version: Option<u16>,
+) -> HttpResponse {
+    let HttpResponse {
+        body,
+        headers,
+        status_code,
+        streaming_strategy,
+        upgrade,
+    } = uncertified_options_response();
+
+    let path = url_path(url);
+
+    // The response is served uncertified if the certificate cannot be provided
+    let certificate_headers =
+        build_certified_path_headers(&path, certificate_version).unwrap_or_default();
+
+    HttpResponse {
+        body,
+        headers: [
+            headers,
+            build_preflight_headers(&path, req_headers),
+            certificate_headers,
+        ]
+        .concat(),
+        status_code,
+        streaming_strategy,
+        upgrade,
+    }
+}
+
+// The answer to OPTIONS without the headers of the preflight and the certificate. It is the same for all the paths.
+pub fn uncertified_options_response() -> HttpResponse {
+    HttpResponse {
+        body: Vec::new(),
+        headers: vec![HeaderField("allow".to_string(), HTTP_METHODS.join(", "))],
+        status_code: 204,
+        streaming_strategy: None,
+        upgrade: None,
+    }
+}
+
+// The headers allowing a preflight request. These depend on the request and are excluded from the certification.
+fn build_preflight_headers(path: &str, req_headers: &[HeaderField]) -> Vec<HeaderField> {
+    let mut headers = vec![];
+
+    let cors = get_config_cors(path)
+        .and_then(|cors| allowed_origin(&cors, req_headers).map(|origin| (cors, origin)));
+
+    if let (Some((cors, origin)), Some(_)) = (
+        cors,
+        find_header(req_headers, "access-control-request-method"),
+    ) {
+        let methods = match cors.allow_methods.is_empty() {
+            true => HTTP_METHODS.join(", "),
+            false => cors.allow_methods.join(", "),
+        };
+
+        headers.push(HeaderField(
+            "access-control-allow-origin".to_string(),
+            origin,
+        ));
+        headers.push(HeaderField(
+            "access-control-allow-methods".to_string(),
+            methods,
+        ));
+
+        if !cors.allow_headers.is_empty() {
+            headers.push(HeaderField(
+                "access-control-allow-headers".to_string(),
+                cors.allow_headers.join(", "),
+            ));
+        }
+
+        if let Some(max_age) = cors.max_age {
+            headers.push(HeaderField(
+                "access-control-max-age".to_string(),
+                max_age.to_string(),
+            ));
+        }
+    }
+
+    headers
+}
+
+fn allowed_origin(cors: &StorageConfigCorsRule, req_headers: &[HeaderField]) -> Option<String> {
+    let origin = find_header(req_headers, "origin")?;
+
+    if cors.allow_origins.iter().any(|allowed| allowed == "*") {
+        return Some("*".to_string());
+    }
+
+    cors.allow_origins
+        .iter()
+        .find(|allowed| allowed.eq_ignore_ascii_case(origin))
+        .cloned()
+}
+
+// The patterns of the config are matched against the path of the url, without its query parameters
+fn url_path(url: &str) -> String {
+    map_url(url).map_or(url.to_string(), |MapUrl { path, .. }| path)
+}
+
+pub fn find_header<'a>(headers: &'a [HeaderField], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|HeaderField(header_name, _)| header_name.eq_ignore_ascii_case(name))
+        .map(|HeaderField(_, value)| value.as_str())
+}
+
+fn merge_headers(headers: &[Vec<HeaderField>]) -> Vec<HeaderField> {
+    headers.iter().flatten().fold(vec![], |mut merged, header| {
+        merged.retain(|HeaderField(name, _)| !name.eq_ignore_ascii_case(&header.0));
+        merged.push(header.clone());
+        merged
+    })
 }
 
 // Source: NNS-dapp
@@ -79,30 +328,227 @@ pub fn build_headers(
 /// iFrame policies, etc.).
 fn security_headers() -> Vec<HeaderField> {
     vec![
-        HeaderField("X-Frame-Options".to_string(), "DENY".to_string()),
-        HeaderField("X-Content-Type-Options".to_string(), "nosniff".to_string()),
+        HeaderField("x-frame-options".to_string(), "DENY".to_string()),
+        HeaderField("x-content-type-options".to_string(), "nosniff".to_string()),
         HeaderField(
-            "Strict-Transport-Security".to_string(),
+            "strict-transport-security".to_string(),
             "max-age=31536000 ; includeSubDomains".to_string(),
         ),
         // "Referrer-Policy: no-referrer" would be more strict, but breaks local dev deployment
         // same-origin is still ok from a security perspective
-        HeaderField("Referrer-Policy".to_string(), "same-origin".to_string()),
+        HeaderField("referrer-policy".to_string(), "same-origin".to_string()),
     ]
 }
 
-pub fn build_encodings(headers: Vec<HeaderField>) -> Vec<String> {
-    let mut encodings: Vec<String> = vec![];
-    for HeaderField(name, value) in headers.iter() {
-        if name.eq_ignore_ascii_case("Accept-Encoding") {
-            for v in value.split(',') {
-                encodings.push(v.trim().to_string());
-            }
-        }
+/// The encodings acceptable for the request, from the most to the least preferred.
+/// The preference of the request - its q-values - comes first, the one of the server breaks the ties. The order of the header is not a preference.
+/// Without "Accept-Encoding" any encoding is acceptable but the content is preferably served uncompressed.
+// The encodings accepted by the request whose response is certified. Response verification v1 certifies the identity encoding, the asset is served in an encoding its verifier decodes only if it has one.
+pub fn build_certified_encodings(
+    headers: &[HeaderField],
+    asset: &Asset,
+    certificate_version: Option<u16>,
+) -> Vec<String> {
+    let encodings = build_encodings(headers);
+
+    if is_certificate_v2(certificate_version) {
+        return encodings;
+    }
+
+    if !asset.encodings.contains_key(ASSET_ENCODING_NO_COMPRESSION) {
+        return vec![];
     }
-    encodings.push(ASSET_ENCODING_NO_COMPRESSION.to_string());
 
     encodings
+        .into_iter()
+        .filter(|encoding_type| CERTIFICATE_V1_ENCODINGS.contains(&encoding_type.as_str()))
+        .collect()
+}
+
+pub fn build_encodings(headers: &[HeaderField]) -> Vec<String> {
+    let accept_encoding: Vec<&str> = headers
+        .iter()
+        .filter(|HeaderField(name, _)| name.eq_ignore_ascii_case("accept-encoding"))
+        .map(|HeaderField(_, value)| value.as_str())
+        .collect();
+
+    if accept_encoding.is_empty() {
+        return std::iter::once(ASSET_ENCODING_NO_COMPRESSION)
+            .chain(
+                ENCODING_PREFERENCE_ORDER
+                    .iter()
+                    .copied()
+                    .filter(|encoding_type| *encoding_type != ASSET_ENCODING_NO_COMPRESSION),
+            )
+            .map(|encoding_type| encoding_type.to_string())
+            .collect();
+    }
+
+    let codings: Vec<(String, u16)> = accept_encoding
+        .iter()
+        .flat_map(|value| value.split(','))
+        .filter_map(parse_coding)
+        .collect();
+
+    let mut encodings: Vec<(usize, u16, &str)> = ENCODING_PREFERENCE_ORDER
+        .iter()
+        .enumerate()
+        .filter_map(|(preference, encoding_type)| {
+            coding_qvalue(&codings, encoding_type)
+                .filter(|qvalue| *qvalue > 0)
+                .map(|qvalue| (preference, qvalue, *encoding_type))
+        })
+        .collect();
+
+    encodings.sort_by(|(preference_a, qvalue_a, _), (preference_b, qvalue_b, _)| {
+        qvalue_b.cmp(qvalue_a).then(preference_a.cmp(preference_b))
+    });
+
+    encodings
+        .into_iter()
+        .map(|(_, _, encoding_type)| encoding_type.to_string())
+        .collect()
+}
+
+// The q-value of an encoding: the one of its coding, otherwise the one of the wildcard. Identity is acceptable unless excluded, as a last resort.
+fn coding_qvalue(codings: &[(String, u16)], encoding_type: &str) -> Option<u16> {
+    let find = |name: &str| {
+        codings
+            .iter()
+            .find(|(coding, _)| coding == name)
+            .map(|(_, qvalue)| *qvalue)
+    };
+
+    find(encoding_type)
+        .or_else(|| find("*"))
+        .or_else(|| (encoding_type == ASSET_ENCODING_NO_COMPRESSION).then_some(1))
+}
+
+// A coding and its q-value in thousandths - e.g. "gzip;q=0.8" -> ("gzip", 800). A coding with an invalid q-value is ignored.
+fn parse_coding(coding: &str) -> Option<(String, u16)> {
+    let mut params = coding.split(';');
+
+    let name = params.next()?.trim().to_ascii_lowercase();
+
+    if name.is_empty() {
+        return None;
+    }
+
+    let qvalue = match params
+        .filter_map(|param| param.split_once('='))
+        .find(|(key, _)| key.trim().eq_ignore_ascii_case("q"))
+    {
+        None => 1000,
+        Some((_, value)) => parse_qvalue(value.trim())?,
+    };
+
+    // Legacy aliases of RFC 9110
+    let name = match name.as_str() {
+        "x-gzip" => "gzip".to_string(),
+        "x-compress" => "compress".to_string(),
+        _ => name,
+    };
+
+    Some((name, qvalue))
+}
+
+// A q-value is a number between 0 and 1 with up to three decimals
+fn parse_qvalue(value: &str) -> Option<u16> {
+    let (integer, decimals) = value.split_once('.').unwrap_or((value, ""));
+
+    if decimals.len() > 3 || !decimals.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let thousandths: u16 = format!("{:0<3}", decimals).parse().ok()?;
+
+    match integer {
+        "0" => Some(thousandths),
+        "1" if thousandths == 0 => Some(1000),
+        _ => None,
+    }
+}
+
+pub fn redirect_response(
+    url: &str,
+    redirect: &StorageConfigRedirect,
+    certificate_version: Option<u16>,
+) -> HttpResponse {
+    let HttpResponse {
+        body,
+        headers,
+        status_code,
+        streaming_strategy,
+        upgrade,
+    } = uncertified_redirect_response(redirect);
+
+    // The response is served uncertified if the certificate cannot be provided
+    let certificate_headers =
+        build_certified_path_headers(url, certificate_version).unwrap_or_default();
+
+    HttpResponse {
+        body,
+        headers: [headers, certificate_headers].concat(),
+        status_code,
+        streaming_strategy,
+        upgrade,
+    }
+}
+
+// The response of a redirect, without the certificate. It is the same for all the paths matching the redirect.
+pub fn uncertified_redirect_response(
+    StorageConfigRedirect {
+        location,
+        status_code,
+    }: &StorageConfigRedirect,
+) -> HttpResponse {
+    HttpResponse {
+        body: Vec::new(),
+        headers: merge_headers(&[
+            security_headers(),
+            vec![HeaderField("location".to_string(), location.clone())],
+        ]),
+        status_code: *status_code,
+        streaming_strategy: None,
+        upgrade: None,
+    }
+}
+
+pub fn not_found_response(url: &str, certificate_version: Option<u16>) -> HttpResponse {
+    let HttpResponse {
+        body,
+        headers,
+        status_code,
+        streaming_strategy,
+        upgrade,
+    } = uncertified_not_found_response();
+
+    // The response is served uncertified if the certificate cannot be provided
+    let certificate_headers =
+        build_certified_wildcard_headers(url, certificate_version).unwrap_or_default();
+
+    HttpResponse {
+        body,
+        headers: [headers, certificate_headers].concat(),
+        status_code,
+        streaming_strategy,
+        upgrade,
+    }
+}
+
+pub fn uncertified_not_found_response() -> HttpResponse {
+    error_response(404, ERROR_ASSET_NOT_FOUND.to_string())
+}
+
+// Ask the boundary node to send the request again as an update call, which can write what the query cannot serve
+pub fn upgrade_response() -> HttpResponse {
+    HttpResponse {
+        body: Vec::new(),
+        headers: Vec::new(),
+        status_code: 200,
+        streaming_strategy: None,
+        upgrade: Some(true),
+    }
 }
 
 pub fn error_response(status_code: u16, body: String) -> HttpResponse {
@@ -111,5 +557,118 @@ pub fn error_response(status_code: u16, body: String) -> HttpResponse {
         headers: Vec::new(),
         status_code,
         streaming_strategy: None,
+        upgrade: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candid::Principal;
+    use std::collections::HashMap;
+
+    fn accept_encoding(value: &str) -> Vec<String> {
+        build_encodings(&[HeaderField(
+            "accept-encoding".to_string(),
+            value.to_string(),
+        )])
+    }
+
+    #[test]
+    fn parses_qvalues() {
+        assert_eq!(parse_qvalue("0"), Some(0));
+        assert_eq!(parse_qvalue("0.8"), Some(800));
+        assert_eq!(parse_qvalue("0.125"), Some(125));
+        assert_eq!(parse_qvalue("1"), Some(1000));
+        assert_eq!(parse_qvalue("1.000"), Some(1000));
+        assert_eq!(parse_qvalue("1.5"), None);
+        assert_eq!(parse_qvalue("0.1234"), None);
+        assert_eq!(parse_qvalue("2"), None);
+        assert_eq!(parse_qvalue("abc"), None);
+    }
+
+    #[test]
+    fn parses_codings() {
+        assert_eq!(parse_coding("gzip"), Some(("gzip".to_string(), 1000)));
+        assert_eq!(parse_coding(" BR ; q=0.5"), Some(("br".to_string(), 500)));
+        assert_eq!(parse_coding("gzip;q=0"), Some(("gzip".to_string(), 0)));
+        assert_eq!(parse_coding("*"), Some(("*".to_string(), 1000)));
+        assert_eq!(parse_coding("x-gzip"), Some(("gzip".to_string(), 1000)));
+        assert_eq!(parse_coding("gzip;q=2"), None);
+        assert_eq!(parse_coding(""), None);
+    }
+
+    #[test]
+    fn excludes_encodings_with_zero_qvalue() {
+        assert_eq!(accept_encoding("gzip;q=0, br"), vec!["br", "identity"]);
+        assert_eq!(accept_encoding("identity;q=0, gzip"), vec!["gzip"]);
+    }
+
+    #[test]
+    fn applies_wildcard_to_other_encodings() {
+        assert_eq!(
+            accept_encoding("*"),
+            vec!["br", "gzip", "deflate", "compress", "identity"]
+        );
+        assert_eq!(accept_encoding("gzip, *;q=0"), vec!["gzip"]);
+        assert_eq!(
+            accept_encoding("br;q=0.5, *"),
+            vec!["gzip", "deflate", "compress", "identity", "br"]
+        );
+    }
+
+    fn asset(encoding_types: &[&str]) -> Asset {
+        let encoding = AssetEncoding {
+            modified: 0,
+            content_chunks: vec![],
+            content_chunk_lengths: vec![],
+            total_length: 0,
+            sha256: [0; 32],
+            decompressed: false,
+        };
+
+        Asset {
+            key: AssetKey {
+                name: "index.html".to_string(),
+                full_path: "/index.html".to_string(),
+                token: None,
+                collection: "#dapp".to_string(),
+                owner: Principal::anonymous(),
+                description: None,
+            },
+            headers: vec![],
+            encodings: encoding_types
+                .iter()
+                .map(|encoding_type| (encoding_type.to_string(), encoding.clone()))
+                .collect::<HashMap<String, AssetEncoding>>(),
+            created_at: 0,
+            updated_at: 0,
+        }
+    }
+
+    #[test]
+    fn serves_v1_encodings_decoded_to_identity() {
+        let headers = [HeaderField(
+            "accept-encoding".to_string(),
+            "br, gzip, compress".to_string(),
+        )];
+
+        assert_eq!(
+            build_certified_encodings(&headers, &asset(&["identity", "br", "gzip"]), None),
+            vec!["gzip", "identity"]
+        );
+        assert_eq!(
+            build_certified_encodings(&headers, &asset(&["identity", "br", "gzip"]), Some(2)),
+            vec!["br", "gzip", "compress", "identity"]
+        );
+        assert!(build_certified_encodings(&headers, &asset(&["compress"]), None).is_empty());
+    }
+
+    #[test]
+    fn prefers_identity_without_accept_encoding() {
+        assert_eq!(
+            build_encodings(&[]),
+            vec!["identity", "br", "gzip", "deflate", "compress"]
+        );
     }
 }
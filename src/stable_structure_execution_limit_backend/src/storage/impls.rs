@@ -1,69 +1,763 @@
-use crate::shared::serializers::{deserialize_from_bytes, serialize_to_bytes};
-use ic_cdk::api::time;
+use crate::shared::serializers::{deserialize_payload, read_version, serialize_versioned_to_bytes};
+use brotli::{CompressorWriter, Decompressor};
+use flate2::read::{GzDecoder, ZlibDecoder};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ic_cdk::api::trap;
+use ic_certified_map::{fork, leaf_hash, AsHashTree, Hash, HashTree, RbTree};
 use ic_stable_structures::storable::Bound;
 use ic_stable_structures::Storable;
-use sha2::{Digest, Sha256};
 use std::borrow::Cow;
 use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind, Read, Write};
 
-use crate::storage::types::interface::AssetNoContent;
-use crate::storage::types::state::StableFullPath;
-use crate::storage::types::store::{Asset, AssetEncoding};
+use crate::storage::constants::{
+    ASSET_VERSION, BATCH_VERSION, BLOB_VERSION, BROTLI_QUALITY, BROTLI_WINDOW, CHUNK_KEY_VERSION,
+    CHUNK_VERSION, COLLECTION_ASSET_KEY_VERSION, COMMIT_VERSION, FULL_PATH_VERSION,
+    LABEL_ASSETS_V1, STORAGE_CONFIG_VERSION, UPLOAD_IDS_VERSION, WRITE_POLICY_VERSION,
+};
+use crate::storage::state::get_content_chunk;
+use crate::storage::types::config::StorageConfig;
+use crate::storage::types::interface::{AssetEncodingNoContent, AssetNoContent};
+use crate::storage::types::legacy::LegacyAsset;
+use crate::storage::types::state::{
+    AssetHashes, ConfigGlobs, FullPath, NestedTree, StableBlob, StableCollectionAssetKey,
+    StableEncodingChunkKey, StableFullPath, StableUploadIds, StorageConfigGlobs,
+};
+use crate::storage::types::store::{
+    Asset, Batch, BatchCommit, Chunk, ContentDecoder, ContentEncoder, ContentReader, WritePolicy,
+};
 use crate::types::core::Compare;
 
-impl From<&Vec<Vec<u8>>> for AssetEncoding {
-    fn from(content_chunks: &Vec<Vec<u8>>) -> Self {
-        let mut total_length: u128 = 0;
-        let mut hasher = Sha256::new();
+impl Compare for AssetNoContent {
+    fn cmp_updated_at(&self, other: &Self) -> Ordering {
+        self.updated_at.cmp(&other.updated_at)
+    }
 
-        // Calculate sha256 and total length
-        for chunk in content_chunks.iter() {
-            total_length += u128::try_from(chunk.len()).unwrap();
+    fn cmp_created_at(&self, other: &Self) -> Ordering {
+        self.created_at.cmp(&other.created_at)
+    }
+}
 
-            hasher.update(chunk);
+impl From<&Asset> for AssetNoContent {
+    fn from(asset: &Asset) -> Self {
+        AssetNoContent {
+            key: asset.key.clone(),
+            headers: asset.headers.clone(),
+            encodings: asset
+                .encodings
+                .iter()
+                .map(|(encoding_type, encoding)| {
+                    (
+                        encoding_type.clone(),
+                        AssetEncodingNoContent {
+                            modified: encoding.modified,
+                            total_length: encoding.total_length,
+                            sha256: encoding.sha256,
+                        },
+                    )
+                })
+                .collect(),
+            created_at: asset.created_at,
+            updated_at: asset.updated_at,
         }
+    }
+}
+
+impl ContentEncoder {
+    pub fn new(encoding_type: &str) -> Result<Self, String> {
+        match encoding_type {
+            "gzip" => Ok(ContentEncoder::Gzip(GzEncoder::new(
+                Vec::new(),
+                Compression::default(),
+            ))),
+            "br" => Ok(ContentEncoder::Brotli(Box::new(CompressorWriter::new(
+                Vec::new(),
+                4096,
+                BROTLI_QUALITY,
+                BROTLI_WINDOW,
+            )))),
+            _ => Err(format!("Encoding {} cannot be compressed.", encoding_type)),
+        }
+    }
 
-        let sha256 = hasher.finalize().into();
+    pub fn write(&mut self, content: &[u8]) -> Result<(), String> {
+        match self {
+            ContentEncoder::Gzip(encoder) => encoder.write_all(content),
+            ContentEncoder::Brotli(encoder) => encoder.write_all(content),
+        }
+        .map_err(|err| ["Cannot compress content. ", &err.to_string()].join(""))
+    }
 
-        AssetEncoding {
-            modified: time(),
-            content_chunks: content_chunks.clone(),
-            total_length,
-            sha256,
+    // The content compressed so far, removed from the encoder
+    pub fn take_output(&mut self) -> Vec<u8> {
+        match self {
+            ContentEncoder::Gzip(encoder) => std::mem::take(encoder.get_mut()),
+            ContentEncoder::Brotli(encoder) => std::mem::take(encoder.get_mut()),
+        }
+    }
+
+    // The remaining compressed content, once the end of the stream is written
+    pub fn finish(self) -> Result<Vec<u8>, String> {
+        match self {
+            ContentEncoder::Gzip(encoder) => encoder
+                .finish()
+                .map_err(|err| ["Cannot compress content. ", &err.to_string()].join("")),
+            ContentEncoder::Brotli(encoder) => Ok(encoder.into_inner()),
         }
     }
 }
 
-impl Compare for AssetNoContent {
-    fn cmp_updated_at(&self, other: &Self) -> Ordering {
-        self.updated_at.cmp(&other.updated_at)
+impl ContentDecoder {
+    pub fn new(
+        encoding_type: &str,
+        content_chunks: Vec<StableEncodingChunkKey>,
+    ) -> Result<Self, String> {
+        let reader = ContentReader {
+            content_chunks,
+            chunk_index: 0,
+            chunk: Vec::new(),
+            offset: 0,
+        };
+
+        match encoding_type {
+            "gzip" => Ok(ContentDecoder::Gzip(GzDecoder::new(reader))),
+            "deflate" => Ok(ContentDecoder::Deflate(ZlibDecoder::new(reader))),
+            "br" => Ok(ContentDecoder::Brotli(Box::new(Decompressor::new(
+                reader, 4096,
+            )))),
+            _ => Err(format!(
+                "Encoding {} cannot be decompressed.",
+                encoding_type
+            )),
+        }
     }
 
-    fn cmp_created_at(&self, other: &Self) -> Ordering {
-        self.created_at.cmp(&other.created_at)
+    // Fill the buffer with the next decompressed content. Less than its length is read only at the end of the content.
+    pub fn read(&mut self, buffer: &mut [u8]) -> Result<usize, String> {
+        let mut length: usize = 0;
+
+        while length < buffer.len() {
+            let read = match self {
+                ContentDecoder::Gzip(decoder) => decoder.read(&mut buffer[length..]),
+                ContentDecoder::Deflate(decoder) => decoder.read(&mut buffer[length..]),
+                ContentDecoder::Brotli(decoder) => decoder.read(&mut buffer[length..]),
+            }
+            .map_err(|err| ["Cannot decompress content. ", &err.to_string()].join(""))?;
+
+            if read == 0 {
+                break;
+            }
+
+            length += read;
+        }
+
+        Ok(length)
+    }
+
+    // The number of chunks of the compressed content read so far
+    pub fn read_chunks(&self) -> usize {
+        match self {
+            ContentDecoder::Gzip(decoder) => decoder.get_ref().chunk_index,
+            ContentDecoder::Deflate(decoder) => decoder.get_ref().chunk_index,
+            ContentDecoder::Brotli(decoder) => decoder.get_ref().chunk_index,
+        }
+    }
+}
+
+impl Read for ContentReader {
+    fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+        while self.offset == self.chunk.len() {
+            let Some(key) = self.content_chunks.get(self.chunk_index) else {
+                return Ok(0);
+            };
+
+            self.chunk = get_content_chunk(key).ok_or_else(|| {
+                Error::new(ErrorKind::NotFound, "Content to decompress does not exist.")
+            })?;
+            self.chunk_index += 1;
+            self.offset = 0;
+        }
+
+        let length = buffer.len().min(self.chunk.len() - self.offset);
+
+        buffer[..length].copy_from_slice(&self.chunk[self.offset..self.offset + length]);
+        self.offset += length;
+
+        Ok(length)
+    }
+}
+
+// No pattern matches with the default, whatever the values
+impl<T> Default for ConfigGlobs<T> {
+    fn default() -> Self {
+        ConfigGlobs {
+            globs: GlobSet::default(),
+            values: Vec::new(),
+        }
+    }
+}
+
+impl<T: Clone> ConfigGlobs<T> {
+    pub fn new(patterns: &HashMap<String, T>) -> Result<Self, String> {
+        let mut sorted: Vec<(&String, &T)> = patterns.iter().collect();
+        sorted.sort_by_key(|(pattern, _)| glob_specificity(pattern));
+
+        let mut builder = GlobSetBuilder::new();
+
+        for (pattern, _) in sorted.iter() {
+            let glob = Glob::new(pattern)
+                .map_err(|err| format!("Invalid glob pattern {}. {}", pattern, err))?;
+
+            builder.add(glob);
+        }
+
+        let globs = builder.build().map_err(|err| err.to_string())?;
+
+        Ok(ConfigGlobs {
+            globs,
+            values: sorted.into_iter().map(|(_, value)| value.clone()).collect(),
+        })
+    }
+
+    // The values of the patterns matching the path, from the least to the most specific
+    pub fn matches(&self, path: &str) -> Vec<&T> {
+        self.globs
+            .matches(path)
+            .into_iter()
+            .filter_map(|index| self.values.get(index))
+            .collect()
+    }
+}
+
+impl StorageConfigGlobs {
+    pub fn new(
+        StorageConfig {
+            headers,
+            rewrites,
+            redirects,
+            cors,
+            ..
+        }: &StorageConfig,
+    ) -> Result<Self, String> {
+        Ok(StorageConfigGlobs {
+            headers: ConfigGlobs::new(headers)?,
+            rewrites: ConfigGlobs::new(rewrites)?,
+            redirects: ConfigGlobs::new(redirects)?,
+            cors: ConfigGlobs::new(cors)?,
+        })
+    }
+}
+
+// A pattern with more literal characters is more specific - e.g. /assets/** is more specific than /**
+fn glob_specificity(pattern: &str) -> (usize, usize, String) {
+    let literals = pattern
+        .chars()
+        .filter(|c| !matches!(c, '*' | '?' | '[' | ']' | '{' | '}'))
+        .count();
+
+    (literals, pattern.len(), pattern.to_string())
+}
+
+impl AssetHashes {
+    // Response verification v1: the sha256 of the content served for a path
+    pub fn insert(&mut self, path: &FullPath, sha256: Hash) {
+        self.tree.insert(&asset_path(path), sha256.to_vec());
+    }
+
+    pub fn delete(&mut self, path: &FullPath) {
+        self.tree.delete(&asset_path(path));
+    }
+
+    // The proof of the paths looked up to verify a response, the requested path and the path a verifier falls back to
+    pub fn witness(&self, paths: &[FullPath]) -> HashTree<'_> {
+        paths
+            .iter()
+            .map(|path| self.tree.witness(&asset_path(path)))
+            .reduce(merge_hash_trees)
+            .unwrap_or_else(|| HashTree::Pruned(self.root_hash()))
+    }
+
+    // Response verification v2: the hashes of the responses served for the path of a certification expression
+    pub fn insert_responses(&mut self, expr_path: &[String], expr_hash: &Hash, responses: &[Hash]) {
+        // The responses previously certified for the path are replaced
+        self.delete_responses(expr_path);
+
+        for response_hash in responses.iter() {
+            let path = [
+                labels(expr_path),
+                vec![expr_hash.to_vec(), vec![], response_hash.to_vec()],
+            ]
+            .concat();
+
+            self.tree.insert(&path, vec![]);
+        }
+    }
+
+    pub fn delete_responses(&mut self, expr_path: &[String]) {
+        self.tree.delete(&labels(expr_path));
+    }
+
+    pub fn contains_responses(&self, expr_path: &[String]) -> bool {
+        self.tree.contains(&labels(expr_path))
+    }
+
+    // The proof of the expression paths - these exist or not - that are looked up to verify a response
+    pub fn witness_responses(&self, expr_paths: &[Vec<String>]) -> HashTree<'_> {
+        expr_paths
+            .iter()
+            .map(|expr_path| self.tree.witness(&labels(expr_path)))
+            .reduce(merge_hash_trees)
+            .unwrap_or_else(|| HashTree::Pruned(self.root_hash()))
+    }
+
+    pub fn root_hash(&self) -> Hash {
+        self.tree.root_hash()
+    }
+}
+
+fn asset_path(path: &FullPath) -> Vec<Vec<u8>> {
+    vec![
+        LABEL_ASSETS_V1.as_bytes().to_vec(),
+        path.as_bytes().to_vec(),
+    ]
+}
+
+fn labels(expr_path: &[String]) -> Vec<Vec<u8>> {
+    expr_path
+        .iter()
+        .map(|label| label.as_bytes().to_vec())
+        .collect()
+}
+
+// Witnesses of the same tree have the same shape, the branches pruned in one are provided by the other
+fn merge_hash_trees<'a>(lhs: HashTree<'a>, rhs: HashTree<'a>) -> HashTree<'a> {
+    match (lhs, rhs) {
+        (HashTree::Pruned(_), tree) | (tree, HashTree::Pruned(_)) => tree,
+        (HashTree::Fork(lhs), HashTree::Fork(rhs)) => {
+            let (lhs_left, lhs_right) = *lhs;
+            let (rhs_left, rhs_right) = *rhs;
+
+            fork(
+                merge_hash_trees(lhs_left, rhs_left),
+                merge_hash_trees(lhs_right, rhs_right),
+            )
+        }
+        (HashTree::Labeled(label, lhs), HashTree::Labeled(_, rhs)) => {
+            HashTree::Labeled(label, Box::new(merge_hash_trees(*lhs, *rhs)))
+        }
+        (tree, _) => tree,
+    }
+}
+
+impl Default for NestedTree {
+    fn default() -> Self {
+        NestedTree::Nested(RbTree::new())
+    }
+}
+
+impl AsHashTree for NestedTree {
+    fn root_hash(&self) -> Hash {
+        match self {
+            NestedTree::Leaf(value) => leaf_hash(value),
+            NestedTree::Nested(tree) => tree.root_hash(),
+        }
+    }
+
+    fn as_hash_tree(&self) -> HashTree<'_> {
+        match self {
+            NestedTree::Leaf(value) => HashTree::Leaf(Cow::from(&value[..])),
+            NestedTree::Nested(tree) => tree.as_hash_tree(),
+        }
+    }
+}
+
+impl NestedTree {
+    pub fn insert(&mut self, path: &[Vec<u8>], value: Vec<u8>) {
+        let Some((key, rest)) = path.split_first() else {
+            *self = NestedTree::Leaf(value);
+            return;
+        };
+
+        if let NestedTree::Leaf(_) = self {
+            *self = NestedTree::default();
+        }
+
+        if let NestedTree::Nested(tree) = self {
+            if tree.get(key).is_none() {
+                tree.insert(key.clone(), NestedTree::default());
+            }
+
+            tree.modify(key, |child| child.insert(rest, value));
+        }
+    }
+
+    pub fn delete(&mut self, path: &[Vec<u8>]) {
+        let (NestedTree::Nested(tree), Some((key, rest))) = (self, path.split_first()) else {
+            return;
+        };
+
+        if !rest.is_empty() {
+            tree.modify(key, |child| child.delete(rest));
+        }
+
+        // Branches left without leaves are removed as well
+        if rest.is_empty() || tree.get(key).is_some_and(NestedTree::is_empty) {
+            tree.delete(key);
+        }
+    }
+
+    pub fn witness(&self, path: &[Vec<u8>]) -> HashTree<'_> {
+        match (self, path.split_first()) {
+            (NestedTree::Nested(tree), Some((key, rest))) => {
+                tree.nested_witness(key, |child| child.witness(rest))
+            }
+            _ => self.as_hash_tree(),
+        }
+    }
+
+    pub fn contains(&self, path: &[Vec<u8>]) -> bool {
+        match (self, path.split_first()) {
+            (NestedTree::Nested(tree), Some((key, rest))) => {
+                tree.get(key).is_some_and(|child| child.contains(rest))
+            }
+            (_, None) => true,
+            (NestedTree::Leaf(_), Some(_)) => false,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        matches!(self, NestedTree::Nested(tree) if tree.is_empty())
     }
 }
 
 impl Storable for Asset {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        serialize_to_bytes(self)
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        serialize_versioned_to_bytes(ASSET_VERSION, self)
     }
 
     fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        deserialize_from_bytes(bytes)
+        decode_asset(&bytes).unwrap_or_else(|err| trap(&err))
     }
 
     const BOUND: Bound = Bound::Unbounded;
 }
 
 impl Storable for StableFullPath {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        serialize_to_bytes(self)
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        serialize_versioned_to_bytes(FULL_PATH_VERSION, self)
     }
 
     fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        deserialize_from_bytes(bytes)
+        decode_full_path(&bytes).unwrap_or_else(|err| trap(&err))
     }
 
     const BOUND: Bound = Bound::Unbounded;
 }
+
+impl Storable for StableCollectionAssetKey {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        serialize_versioned_to_bytes(COLLECTION_ASSET_KEY_VERSION, self)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_collection_asset_key(&bytes).unwrap_or_else(|err| trap(&err))
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+impl Storable for StableEncodingChunkKey {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        serialize_versioned_to_bytes(CHUNK_KEY_VERSION, self)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_chunk_key(&bytes).unwrap_or_else(|err| trap(&err))
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+impl Storable for StableBlob {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        serialize_versioned_to_bytes(BLOB_VERSION, self)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_blob(&bytes).unwrap_or_else(|err| trap(&err))
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+impl Storable for Batch {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        serialize_versioned_to_bytes(BATCH_VERSION, self)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_batch(&bytes).unwrap_or_else(|err| trap(&err))
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+impl Storable for Chunk {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        serialize_versioned_to_bytes(CHUNK_VERSION, self)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_chunk(&bytes).unwrap_or_else(|err| trap(&err))
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+impl Storable for BatchCommit {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        serialize_versioned_to_bytes(COMMIT_VERSION, self)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_commit(&bytes).unwrap_or_else(|err| trap(&err))
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+impl Storable for StableUploadIds {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        serialize_versioned_to_bytes(UPLOAD_IDS_VERSION, self)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_upload_ids(&bytes).unwrap_or_else(|err| trap(&err))
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+impl Storable for WritePolicy {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        serialize_versioned_to_bytes(WRITE_POLICY_VERSION, self)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_write_policy(&bytes).unwrap_or_else(|err| trap(&err))
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+impl Storable for StorageConfig {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        serialize_versioned_to_bytes(STORAGE_CONFIG_VERSION, self)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_storage_config(&bytes).unwrap_or_else(|err| trap(&err))
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Decoders
+
+// When a type changes, its version is incremented and the previous format is decoded and converted here
+
+fn decode_asset(bytes: &[u8]) -> Result<Asset, String> {
+    match read_version(bytes) {
+        (Some(ASSET_VERSION), payload) => deserialize_payload(payload),
+        // Without version, the legacy assets with inline content. Their content is not served until these are converted by the migration.
+        (None, payload) => deserialize_payload::<LegacyAsset>(payload).map(
+            |LegacyAsset {
+                 key,
+                 headers,
+                 created_at,
+                 updated_at,
+                 ..
+             }| Asset {
+                key,
+                headers,
+                encodings: HashMap::new(),
+                created_at,
+                updated_at,
+            },
+        ),
+        (Some(version), _) => Err(format!("Unsupported asset version {}.", version)),
+    }
+}
+
+fn decode_full_path(bytes: &[u8]) -> Result<StableFullPath, String> {
+    match read_version(bytes) {
+        (None, payload) | (Some(FULL_PATH_VERSION), payload) => deserialize_payload(payload),
+        (Some(version), _) => Err(format!("Unsupported full path version {}.", version)),
+    }
+}
+
+fn decode_collection_asset_key(bytes: &[u8]) -> Result<StableCollectionAssetKey, String> {
+    match read_version(bytes) {
+        (Some(COLLECTION_ASSET_KEY_VERSION), payload) => deserialize_payload(payload),
+        (version, _) => Err(format!(
+            "Unsupported collection asset key version {:?}.",
+            version
+        )),
+    }
+}
+
+// The content chunks are searched with the order of their decoded keys, the keys saved without version are found with the latest format and do not have to be rewritten.
+// Without version, the keys saved before the chunks were written for a batch - their batch id defaults to 0.
+fn decode_chunk_key(bytes: &[u8]) -> Result<StableEncodingChunkKey, String> {
+    match read_version(bytes) {
+        (None, payload) | (Some(CHUNK_KEY_VERSION), payload) => deserialize_payload(payload),
+        (Some(version), _) => Err(format!("Unsupported chunk key version {}.", version)),
+    }
+}
+
+fn decode_blob(bytes: &[u8]) -> Result<StableBlob, String> {
+    match read_version(bytes) {
+        (Some(BLOB_VERSION), payload) => deserialize_payload(payload),
+        (version, _) => Err(format!("Unsupported blob version {:?}.", version)),
+    }
+}
+
+// The uploads were kept in heap memory until these were saved in stable memory, there is no unversioned format to decode
+
+fn decode_batch(bytes: &[u8]) -> Result<Batch, String> {
+    match read_version(bytes) {
+        (Some(BATCH_VERSION), payload) => deserialize_payload(payload),
+        (version, _) => Err(format!("Unsupported batch version {:?}.", version)),
+    }
+}
+
+fn decode_chunk(bytes: &[u8]) -> Result<Chunk, String> {
+    match read_version(bytes) {
+        (Some(CHUNK_VERSION), payload) => deserialize_payload(payload),
+        (version, _) => Err(format!("Unsupported chunk version {:?}.", version)),
+    }
+}
+
+fn decode_commit(bytes: &[u8]) -> Result<BatchCommit, String> {
+    match read_version(bytes) {
+        (Some(COMMIT_VERSION), payload) => deserialize_payload(payload),
+        (version, _) => Err(format!("Unsupported commit version {:?}.", version)),
+    }
+}
+
+fn decode_upload_ids(bytes: &[u8]) -> Result<StableUploadIds, String> {
+    match read_version(bytes) {
+        (Some(UPLOAD_IDS_VERSION), payload) => deserialize_payload(payload),
+        (version, _) => Err(format!("Unsupported upload ids version {:?}.", version)),
+    }
+}
+
+fn decode_write_policy(bytes: &[u8]) -> Result<WritePolicy, String> {
+    match read_version(bytes) {
+        (Some(WRITE_POLICY_VERSION), payload) => deserialize_payload(payload),
+        (version, _) => Err(format!("Unsupported write policy version {:?}.", version)),
+    }
+}
+
+fn decode_storage_config(bytes: &[u8]) -> Result<StorageConfig, String> {
+    match read_version(bytes) {
+        (Some(STORAGE_CONFIG_VERSION), payload) => deserialize_payload(payload),
+        (version, _) => Err(format!("Unsupported storage config version {:?}.", version)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::state::insert_content_chunk;
+
+    // Write the content in chunks of the given length, as a commit would
+    fn write_chunks(full_path: &str, content: &[u8], length: usize) -> Vec<StableEncodingChunkKey> {
+        content
+            .chunks(length)
+            .enumerate()
+            .map(|(chunk_index, chunk)| {
+                let key = StableEncodingChunkKey {
+                    full_path: full_path.to_string(),
+                    encoding_type: "gzip".to_string(),
+                    batch_id: 1,
+                    chunk_index,
+                };
+
+                insert_content_chunk(&key, &chunk.to_vec());
+
+                key
+            })
+            .collect()
+    }
+
+    #[test]
+    fn literal_characters_are_more_specific() {
+        assert!(glob_specificity("/assets/**") > glob_specificity("/**"));
+        assert!(glob_specificity("/assets/*.js") > glob_specificity("/assets/**"));
+        assert!(glob_specificity("/index.html") > glob_specificity("/*.html"));
+    }
+
+    #[test]
+    fn equally_specific_patterns_are_ordered() {
+        assert!(glob_specificity("/a/*") < glob_specificity("/a/**"));
+        assert!(glob_specificity("/a/*") < glob_specificity("/b/*"));
+    }
+
+    #[test]
+    fn matches_from_least_to_most_specific() {
+        let patterns = HashMap::from([
+            ("/assets/**".to_string(), "assets"),
+            ("/**".to_string(), "all"),
+            ("/assets/*.js".to_string(), "scripts"),
+        ]);
+
+        let globs = ConfigGlobs::new(&patterns).unwrap();
+
+        assert_eq!(
+            globs.matches("/assets/app.js"),
+            vec![&"all", &"assets", &"scripts"]
+        );
+        assert_eq!(globs.matches("/index.html"), vec![&"all"]);
+    }
+
+    #[test]
+    fn decompresses_content_across_chunks() {
+        let content: Vec<u8> = (0..100_000).map(|i| (i % 251) as u8).collect();
+
+        let mut encoder = ContentEncoder::new("gzip").unwrap();
+        encoder.write(&content).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let chunks = write_chunks("/decompressed.txt", &compressed, 1_000);
+        let total_chunks = chunks.len();
+
+        let mut decoder = ContentDecoder::new("gzip", chunks).unwrap();
+        let mut decompressed: Vec<u8> = Vec::new();
+        let mut buffer = vec![0; 30_000];
+
+        loop {
+            let length = decoder.read(&mut buffer).unwrap();
+            decompressed.extend_from_slice(&buffer[..length]);
+
+            if length < buffer.len() {
+                break;
+            }
+        }
+
+        assert_eq!(decompressed, content);
+        assert_eq!(decoder.read_chunks(), total_chunks);
+    }
+
+    #[test]
+    fn rejects_invalid_content() {
+        let chunks = write_chunks("/invalid.txt", b"not gzip content", 4);
+
+        let mut decoder = ContentDecoder::new("gzip", chunks).unwrap();
+
+        assert!(decoder.read(&mut [0; 1_000]).is_err());
+    }
+}
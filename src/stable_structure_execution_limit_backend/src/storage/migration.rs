@@ -0,0 +1,497 @@
+use crate::memory::get_memory_assets;
+use crate::rules::store::init_collection_rule;
+use crate::shared::api::instruction_counter;
+use crate::shared::serializers::{deserialize_payload, read_version};
+use crate::storage::constants::{ASSET_VERSION, FULL_PATH_VERSION};
+use crate::storage::runtime::{
+    insert_migration as insert_runtime_migration, take_migration as take_runtime_migration,
+};
+use crate::storage::state::{
+    delete_asset as delete_state_asset, get_blob as get_state_blob,
+    index_asset as index_state_asset, insert_asset as insert_state_asset,
+    insert_blob as insert_state_blob, insert_content_chunk as insert_state_content_chunk,
+};
+use crate::storage::types::legacy::{LegacyAsset, LegacyAssetEncoding};
+use crate::storage::types::state::{
+    AssetsMigration, FullPath, StableBlob, StableEncodingChunkKey, StableFullPath,
+};
+use crate::storage::types::store::{Asset, AssetEncoding};
+use crate::types::core::Blob;
+use crate::types::memory::Memory;
+use ic_cdk::api::trap;
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::{StableBTreeMap, Storable};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::ops::Bound as RangeBound;
+
+// A view of the assets as they are saved. The migration has to read the bytes to find the entries written with a previous format.
+// ⚠️ A map is searched with the order of its key type. The key of the view keeps the bytes saved but is ordered with the decoded full path, the order of the map.
+type RawAssetsStable = StableBTreeMap<RawFullPath, Blob, Memory>;
+
+#[derive(Clone)]
+struct RawFullPath {
+    full_path: FullPath,
+    bytes: Blob,
+}
+
+// The content chunks moved out of the legacy assets were never uploaded with a batch. Batch ids are allocated from 1.
+const LEGACY_BATCH_ID: u128 = 0;
+
+// The upgrade converts the assets until this threshold, far below its instruction limit as the certification of the assets follows. An update call continues with the margin of the commits.
+const UPGRADE_INSTRUCTIONS_THRESHOLD: u64 = 100_000_000_000;
+const UPDATE_INSTRUCTIONS_THRESHOLD: u64 = 15_000_000_000;
+
+/// Start the conversion of the assets saved with a previous format to the latest format - first in post_upgrade.
+/// The assets are converted one at a time from a cursor as long as the upgrade does not approach the threshold, migrate_assets converts the remaining ones. Until then, an asset saved with its content inline is not served.
+/// Each asset is also added to the assets of its collection, if it was saved before these were indexed.
+pub fn migrate_stable_memory() {
+    insert_runtime_migration(AssetsMigration::default());
+
+    migrate_assets_until(UPGRADE_INSTRUCTIONS_THRESHOLD);
+}
+
+/// Continue the conversion started by the upgrade. Returns the full paths of the assets converted and whether all are converted.
+pub fn migrate_assets() -> (Vec<FullPath>, bool) {
+    migrate_assets_until(UPDATE_INSTRUCTIONS_THRESHOLD)
+}
+
+fn migrate_assets_until(threshold: u64) -> (Vec<FullPath>, bool) {
+    let Some(mut migration) = take_runtime_migration() else {
+        return (vec![], true);
+    };
+
+    let mut full_paths: Vec<FullPath> = Vec::new();
+
+    while let Some((key, value)) = get_next_raw_asset(&migration.cursor) {
+        if !is_latest(&key.bytes, FULL_PATH_VERSION) || !is_latest(&value, ASSET_VERSION) {
+            migrate_asset(&key.full_path, value);
+            full_paths.push(key.full_path.clone());
+        }
+
+        // The assets saved before the collections were indexed are added to their collection, which is registered if it has no rule
+        if let Some(collection) = index_state_asset(&key.full_path) {
+            init_collection_rule(&collection);
+        }
+
+        migration.cursor = Some(key.full_path);
+
+        if instruction_counter() > threshold {
+            insert_runtime_migration(migration);
+            return (full_paths, false);
+        }
+    }
+
+    (full_paths, true)
+}
+
+// The view is initialized for each asset, as the assets converted are written through the state in between
+fn get_next_raw_asset(cursor: &Option<FullPath>) -> Option<(RawFullPath, Blob)> {
+    let assets: RawAssetsStable = StableBTreeMap::init(get_memory_assets());
+
+    match cursor {
+        None => assets.iter().next(),
+        Some(cursor) => assets
+            .range((
+                RangeBound::Excluded(RawFullPath::from(cursor)),
+                RangeBound::Unbounded,
+            ))
+            .next(),
+    }
+}
+
+fn migrate_asset(full_path: &FullPath, value: Blob) {
+    let asset = match read_version(&value) {
+        (None, payload) => {
+            let legacy =
+                deserialize_payload::<LegacyAsset>(payload).unwrap_or_else(|err| trap(&err));
+            migrate_legacy_asset(legacy)
+        }
+        _ => Asset::from_bytes(Cow::Owned(value)),
+    };
+
+    // The asset is removed first, inserting it again would otherwise only replace the value and keep the outdated bytes of the key
+    delete_state_asset(full_path);
+    insert_state_asset(full_path, &asset);
+}
+
+// Move the content saved inline in the asset to the content chunks and the blobs
+fn migrate_legacy_asset(
+    LegacyAsset {
+        key,
+        headers,
+        encodings,
+        created_at,
+        updated_at,
+    }: LegacyAsset,
+) -> Asset {
+    let encodings = encodings
+        .into_iter()
+        .map(|(encoding_type, encoding)| {
+            let encoding = migrate_legacy_encoding(&key.full_path, &encoding_type, encoding);
+
+            (encoding_type, encoding)
+        })
+        .collect();
+
+    Asset {
+        key,
+        headers,
+        encodings,
+        created_at,
+        updated_at,
+    }
+}
+
+fn migrate_legacy_encoding(
+    full_path: &str,
+    encoding_type: &str,
+    LegacyAssetEncoding {
+        modified,
+        content_chunks: content,
+        total_length,
+        sha256,
+    }: LegacyAssetEncoding,
+) -> AssetEncoding {
+    // The same content was already moved for another asset or encoding
+    let blob = match get_state_blob(&sha256) {
+        Some(blob) => StableBlob {
+            references: blob.references + 1,
+            ..blob
+        },
+        None => {
+            let mut keys: Vec<StableEncodingChunkKey> = Vec::new();
+            let mut lengths: Vec<usize> = Vec::new();
+
+            for (chunk_index, chunk) in content.into_iter().enumerate() {
+                let key = StableEncodingChunkKey {
+                    full_path: full_path.to_string(),
+                    encoding_type: encoding_type.to_string(),
+                    batch_id: LEGACY_BATCH_ID,
+                    chunk_index,
+                };
+
+                lengths.push(chunk.len());
+                insert_state_content_chunk(&key, &chunk);
+                keys.push(key);
+            }
+
+            StableBlob {
+                content_chunks: keys,
+                content_chunk_lengths: lengths,
+                references: 1,
+                decompressed: None,
+            }
+        }
+    };
+
+    insert_state_blob(&sha256, &blob);
+
+    AssetEncoding {
+        modified,
+        content_chunks: blob.content_chunks,
+        content_chunk_lengths: blob.content_chunk_lengths,
+        total_length,
+        sha256,
+        decompressed: false,
+    }
+}
+
+fn is_latest(bytes: &[u8], version: u8) -> bool {
+    read_version(bytes).0 == Some(version)
+}
+
+// A bound of a search, saved with the latest format
+impl From<&FullPath> for RawFullPath {
+    fn from(full_path: &FullPath) -> Self {
+        let bytes = StableFullPath {
+            full_path: full_path.clone(),
+        }
+        .to_bytes()
+        .into_owned();
+
+        RawFullPath {
+            full_path: full_path.clone(),
+            bytes,
+        }
+    }
+}
+
+impl Storable for RawFullPath {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(&self.bytes)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        RawFullPath {
+            full_path: StableFullPath::from_bytes(Cow::Borrowed(&bytes)).full_path,
+            bytes: bytes.into_owned(),
+        }
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+impl PartialEq for RawFullPath {
+    fn eq(&self, other: &Self) -> bool {
+        self.full_path == other.full_path
+    }
+}
+
+impl Eq for RawFullPath {}
+
+impl PartialOrd for RawFullPath {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for RawFullPath {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.full_path.cmp(&other.full_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::get_memory_content_chunks;
+    use crate::shared::api::mock::set_instruction_counter;
+    use crate::storage::state::{
+        get_asset, get_blob, get_content_chunk, get_full_paths, insert_content_chunk,
+    };
+    use crate::storage::types::store::AssetKey;
+    use candid::Principal;
+    use ciborium::{from_reader, into_writer};
+    use ic_stable_structures::storable::Bound;
+    use serde::{Deserialize, Serialize};
+    use sha2::{Digest, Sha256};
+    use std::collections::HashMap;
+
+    // The full path and the asset as saved by the baseline, without version and with the content inline
+    #[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord)]
+    struct BaselineFullPath {
+        full_path: String,
+    }
+
+    struct BaselineAsset(LegacyAsset);
+
+    // The key of the content chunks saved before these were written for a batch, without version
+    #[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord)]
+    struct UnversionedChunkKey {
+        full_path: String,
+        encoding_type: String,
+        chunk_index: usize,
+    }
+
+    fn serialize_to_bytes<T: Serialize>(value: &T) -> Cow<'_, [u8]> {
+        let mut bytes = vec![];
+        into_writer(value, &mut bytes).expect("Failed to serialize to bytes");
+        Cow::Owned(bytes)
+    }
+
+    impl Storable for BaselineFullPath {
+        fn to_bytes(&self) -> Cow<'_, [u8]> {
+            serialize_to_bytes(self)
+        }
+
+        fn from_bytes(bytes: Cow<[u8]>) -> Self {
+            from_reader(&*bytes).expect("Failed to deserialize from bytes")
+        }
+
+        const BOUND: Bound = Bound::Unbounded;
+    }
+
+    impl Storable for UnversionedChunkKey {
+        fn to_bytes(&self) -> Cow<'_, [u8]> {
+            serialize_to_bytes(self)
+        }
+
+        fn from_bytes(bytes: Cow<[u8]>) -> Self {
+            from_reader(&*bytes).expect("Failed to deserialize from bytes")
+        }
+
+        const BOUND: Bound = Bound::Unbounded;
+    }
+
+    impl Storable for BaselineAsset {
+        fn to_bytes(&self) -> Cow<'_, [u8]> {
+            serialize_to_bytes(&self.0)
+        }
+
+        fn from_bytes(bytes: Cow<[u8]>) -> Self {
+            BaselineAsset(from_reader(&*bytes).expect("Failed to deserialize from bytes"))
+        }
+
+        const BOUND: Bound = Bound::Unbounded;
+    }
+
+    fn baseline_asset(full_path: &str, content_chunks: Vec<Vec<u8>>) -> LegacyAsset {
+        let mut hasher = Sha256::new();
+        content_chunks.iter().for_each(|chunk| hasher.update(chunk));
+
+        let encoding = LegacyAssetEncoding {
+            modified: 0,
+            total_length: content_chunks.iter().map(|chunk| chunk.len() as u128).sum(),
+            content_chunks,
+            sha256: hasher.finalize().into(),
+        };
+
+        LegacyAsset {
+            key: AssetKey {
+                name: full_path.to_string(),
+                full_path: full_path.to_string(),
+                token: None,
+                collection: "images".to_string(),
+                owner: Principal::anonymous(),
+                description: None,
+            },
+            headers: vec![],
+            encodings: HashMap::from([("identity".to_string(), encoding)]),
+            created_at: 0,
+            updated_at: 0,
+        }
+    }
+
+    // Enough paths to split the nodes of the map, whose order differs from the one of their bytes
+    fn baseline_paths() -> Vec<String> {
+        ["/b", "/aa", "/c", "/index.html", "/z"]
+            .iter()
+            .map(|path| path.to_string())
+            .chain((0..40).map(|i| format!("/images/{}.jpg", i)))
+            .collect()
+    }
+
+    fn baseline_content(full_path: &str) -> Vec<Vec<u8>> {
+        vec![full_path.as_bytes().to_vec(), vec![0; 100]]
+    }
+
+    fn populate_baseline() {
+        let mut assets: StableBTreeMap<BaselineFullPath, BaselineAsset, Memory> =
+            StableBTreeMap::init(get_memory_assets());
+
+        for path in baseline_paths() {
+            let asset = baseline_asset(&path, baseline_content(&path));
+            assets.insert(BaselineFullPath { full_path: path }, BaselineAsset(asset));
+        }
+
+        // Two assets with the same content
+        assets.insert(
+            BaselineFullPath {
+                full_path: "/copy.jpg".to_string(),
+            },
+            BaselineAsset(baseline_asset("/copy.jpg", baseline_content("/b"))),
+        );
+    }
+
+    fn read_content(full_path: &str) -> Vec<u8> {
+        let asset = get_asset(&full_path.to_string()).expect("Asset not found");
+        let encoding = asset.encodings.get("identity").expect("Encoding not found");
+
+        encoding
+            .content_chunks
+            .iter()
+            .flat_map(|key| get_content_chunk(key).expect("Content chunk not found"))
+            .collect()
+    }
+
+    #[test]
+    fn upgrade_from_baseline_keeps_assets() {
+        populate_baseline();
+
+        migrate_stable_memory();
+        // The entries are up to date, a second upgrade does not rewrite these
+        migrate_stable_memory();
+
+        let paths = baseline_paths();
+
+        assert_eq!(get_full_paths().len(), paths.len() + 1);
+
+        for path in paths {
+            assert_eq!(read_content(&path), baseline_content(&path).concat());
+        }
+
+        assert_eq!(read_content("/copy.jpg"), baseline_content("/b").concat());
+
+        let asset = get_asset(&"/copy.jpg".to_string()).unwrap();
+        let blob = get_blob(&asset.encodings.get("identity").unwrap().sha256).unwrap();
+
+        assert_eq!(blob.references, 2);
+    }
+
+    #[test]
+    fn converts_one_asset_per_call_over_the_threshold() {
+        populate_baseline();
+
+        set_instruction_counter(UPGRADE_INSTRUCTIONS_THRESHOLD + 1);
+
+        migrate_stable_memory();
+
+        // The assets not converted yet are found, without content
+        let full_paths = get_full_paths();
+        let last = full_paths.last().unwrap();
+
+        assert_eq!(
+            read_content(&full_paths[0]),
+            baseline_content(&full_paths[0]).concat()
+        );
+        assert!(get_asset(last).unwrap().encodings.is_empty());
+
+        let mut calls = 1;
+
+        loop {
+            let (migrated, done) = migrate_assets();
+
+            if done {
+                assert!(migrated.is_empty());
+                break;
+            }
+
+            assert_eq!(migrated.len(), 1);
+            calls += 1;
+        }
+
+        assert_eq!(calls, full_paths.len());
+        assert_eq!(read_content(last), baseline_content(last).concat());
+    }
+
+    #[test]
+    fn unversioned_chunk_keys_are_found() {
+        let chunk_key = |full_path: &String, batch_id: u128| StableEncodingChunkKey {
+            full_path: full_path.clone(),
+            encoding_type: "identity".to_string(),
+            batch_id,
+            chunk_index: 0,
+        };
+
+        let paths = baseline_paths();
+
+        {
+            let mut content_chunks: StableBTreeMap<UnversionedChunkKey, Blob, Memory> =
+                StableBTreeMap::init(get_memory_content_chunks());
+
+            for path in paths.iter() {
+                let key = UnversionedChunkKey {
+                    full_path: path.clone(),
+                    encoding_type: "identity".to_string(),
+                    chunk_index: 0,
+                };
+
+                content_chunks.insert(key, path.as_bytes().to_vec());
+            }
+        }
+
+        // New chunks are saved next to the unversioned ones
+        for path in paths.iter() {
+            insert_content_chunk(&chunk_key(path, 1), &vec![1]);
+        }
+
+        for path in paths.iter() {
+            assert_eq!(
+                get_content_chunk(&chunk_key(path, 0)),
+                Some(path.as_bytes().to_vec())
+            );
+            assert_eq!(get_content_chunk(&chunk_key(path, 1)), Some(vec![1]));
+        }
+    }
+}
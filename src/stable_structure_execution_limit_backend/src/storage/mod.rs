@@ -1,6 +1,10 @@
+mod cert;
+pub mod conditional;
 mod constants;
 pub mod http;
 pub mod impls;
+pub mod migration;
+pub mod range;
 mod runtime;
 mod state;
 pub mod store;
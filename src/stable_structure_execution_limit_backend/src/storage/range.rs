@@ -0,0 +1,323 @@
+use hex::encode;
+use ic_cdk::api::data_certificate;
+
+use crate::storage::conditional::matches_if_range;
+use crate::storage::http::{find_header, upgrade_response};
+use crate::storage::store::get_content_chunk;
+use crate::storage::types::http::{ByteRange, HeaderField, HttpResponse};
+use crate::storage::types::store::AssetEncoding;
+
+// Partial responses are not streamed, these have to fit in a single response
+const MAX_RANGE_RESPONSE_LENGTH: u128 = 2_000_000;
+
+// A request with more ranges is served with the full content
+const MAX_RANGES: usize = 16;
+
+/// Build a 206 or 416 response if the request contains a valid "Range" header.
+/// Returns None if the header is missing or cannot be parsed, if "If-Range" does not match the encoding or if the range is the whole content, in which cases the full content should be served.
+/// Only the full response is certified, the partial responses cannot be for all the ranges a request can ask. A query for a part of the content is upgraded to an update call, whose response is certified by the consensus.
+pub fn build_range_response(
+    req_headers: &[HeaderField],
+    encoding: &AssetEncoding,
+    headers: &[HeaderField],
+) -> Option<HttpResponse> {
+    let range = find_header(req_headers, "range")?;
+
+    if !matches_if_range(req_headers, encoding, headers) {
+        return None;
+    }
+
+    let ranges = parse_ranges(range, encoding.total_length)?;
+
+    // e.g. "bytes=0-" requested by the media players, the full response is certified
+    if is_full_content(&ranges, encoding.total_length) {
+        return None;
+    }
+
+    // The certificate is only available in query calls
+    if data_certificate().is_some() {
+        return Some(upgrade_response());
+    }
+
+    match limit_ranges(ranges).as_slice() {
+        [] => Some(unsatisfiable_response(encoding)),
+        [range] => single_range_response(encoding, headers, range),
+        ranges => multipart_response(encoding, headers, ranges),
+    }
+}
+
+/// Parse the value of a "Range" header - e.g. "bytes=0-499", "bytes=500-", "bytes=-500" or "bytes=0-99,200-299".
+/// Returns None if the header is invalid and should be ignored, an empty list if none of the ranges can be satisfied.
+fn parse_ranges(value: &str, total_length: u128) -> Option<Vec<ByteRange>> {
+    let (unit, specs) = value.trim().split_once('=')?;
+
+    if !unit.trim().eq_ignore_ascii_case("bytes") {
+        return None;
+    }
+
+    let mut ranges: Vec<ByteRange> = vec![];
+
+    for spec in specs.split(',') {
+        let (start, end) = spec.trim().split_once('-')?;
+
+        let range = match (start.trim(), end.trim()) {
+            ("", "") => return None,
+            // Suffix range: the last bytes of the content
+            ("", suffix) => {
+                let suffix: u128 = suffix.parse().ok()?;
+
+                if suffix == 0 || total_length == 0 {
+                    continue;
+                }
+
+                ByteRange {
+                    start: total_length.saturating_sub(suffix),
+                    end: total_length - 1,
+                }
+            }
+            (start, end) => {
+                let start: u128 = start.parse().ok()?;
+
+                let end: u128 = match end {
+                    "" => u128::MAX,
+                    end => end.parse().ok()?,
+                };
+
+                if end < start {
+                    return None;
+                }
+
+                if start >= total_length {
+                    continue;
+                }
+
+                ByteRange {
+                    start,
+                    end: end.min(total_length - 1),
+                }
+            }
+        };
+
+        ranges.push(range);
+    }
+
+    if ranges.len() > MAX_RANGES {
+        return None;
+    }
+
+    Some(ranges)
+}
+
+// The ranges are served in a single response. If these are too long, only the beginning of the first range is returned.
+// The response is then a single part whose Content-Range tells the client which bytes it received - RFC 9110 lets a server serve fewer ranges than requested - and the client requests the remaining bytes.
+fn limit_ranges(ranges: Vec<ByteRange>) -> Vec<ByteRange> {
+    let total: u128 = ranges.iter().map(range_length).sum();
+
+    if total <= MAX_RANGE_RESPONSE_LENGTH {
+        return ranges;
+    }
+
+    ranges
+        .first()
+        .map(|ByteRange { start, end }| ByteRange {
+            start: *start,
+            end: (*end).min(start + MAX_RANGE_RESPONSE_LENGTH - 1),
+        })
+        .into_iter()
+        .collect()
+}
+
+fn is_full_content(ranges: &[ByteRange], total_length: u128) -> bool {
+    matches!(ranges, [ByteRange { start: 0, end }] if end + 1 == total_length)
+}
+
+fn range_length(ByteRange { start, end }: &ByteRange) -> u128 {
+    end - start + 1
+}
+
+// Read the bytes of a range which may span multiple chunks
+fn read_range(encoding: &AssetEncoding, range: &ByteRange) -> Option<Vec<u8>> {
+    let mut body: Vec<u8> = vec![];
+    let mut chunk_start: u128 = 0;
+
+    for (chunk_index, length) in encoding.content_chunk_lengths.iter().enumerate() {
+        let chunk_end = chunk_start + u128::try_from(*length).unwrap();
+
+        if chunk_end > range.start && chunk_start <= range.end {
+            let chunk = get_content_chunk(encoding, chunk_index)?;
+
+            let from = usize::try_from(range.start.saturating_sub(chunk_start)).unwrap();
+            let to = usize::try_from((range.end + 1).min(chunk_end) - chunk_start).unwrap();
+
+            body.extend_from_slice(&chunk[from..to]);
+        }
+
+        if chunk_end > range.end {
+            break;
+        }
+
+        chunk_start = chunk_end;
+    }
+
+    Some(body)
+}
+
+fn content_range(ByteRange { start, end }: &ByteRange, total_length: u128) -> String {
+    format!("bytes {}-{}/{}", start, end, total_length)
+}
+
+fn single_range_response(
+    encoding: &AssetEncoding,
+    headers: &[HeaderField],
+    range: &ByteRange,
+) -> Option<HttpResponse> {
+    let body = read_range(encoding, range)?;
+
+    let mut headers = headers.to_owned();
+
+    headers.push(HeaderField(
+        "content-range".to_string(),
+        content_range(range, encoding.total_length),
+    ));
+
+    Some(HttpResponse {
+        body,
+        headers,
+        status_code: 206,
+        streaming_strategy: None,
+        upgrade: None,
+    })
+}
+
+fn multipart_response(
+    encoding: &AssetEncoding,
+    headers: &[HeaderField],
+    ranges: &[ByteRange],
+) -> Option<HttpResponse> {
+    // The sha256 of the content is deterministic - responses of queries are compared across replicas - and does not appear in the content
+    let boundary = encode(encoding.sha256);
+
+    let content_type = find_header(headers, "content-type");
+
+    let mut body: Vec<u8> = vec![];
+
+    for range in ranges.iter() {
+        body.extend_from_slice(format!("--{}\r\n", boundary).as_bytes());
+
+        if let Some(content_type) = content_type {
+            body.extend_from_slice(format!("content-type: {}\r\n", content_type).as_bytes());
+        }
+
+        body.extend_from_slice(
+            format!(
+                "content-range: {}\r\n\r\n",
+                content_range(range, encoding.total_length)
+            )
+            .as_bytes(),
+        );
+        body.extend_from_slice(&read_range(encoding, range)?);
+        body.extend_from_slice(b"\r\n");
+    }
+
+    body.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+
+    let mut headers: Vec<HeaderField> = headers
+        .iter()
+        .filter(|HeaderField(name, _)| !name.eq_ignore_ascii_case("content-type"))
+        .cloned()
+        .collect();
+
+    headers.push(HeaderField(
+        "content-type".to_string(),
+        format!("multipart/byteranges; boundary={}", boundary),
+    ));
+
+    Some(HttpResponse {
+        body,
+        headers,
+        status_code: 206,
+        streaming_strategy: None,
+        upgrade: None,
+    })
+}
+
+fn unsatisfiable_response(encoding: &AssetEncoding) -> HttpResponse {
+    HttpResponse {
+        body: "Range Not Satisfiable.".as_bytes().to_vec(),
+        headers: vec![HeaderField(
+            "content-range".to_string(),
+            format!("bytes */{}", encoding.total_length),
+        )],
+        status_code: 416,
+        streaming_strategy: None,
+        upgrade: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(value: &str, total_length: u128) -> Option<Vec<(u128, u128)>> {
+        parse_ranges(value, total_length).map(|ranges| {
+            ranges
+                .into_iter()
+                .map(|ByteRange { start, end }| (start, end))
+                .collect()
+        })
+    }
+
+    #[test]
+    fn parses_closed_ranges() {
+        assert_eq!(parse("bytes=0-499", 1000), Some(vec![(0, 499)]));
+        assert_eq!(
+            parse("bytes=0-99, 200-299", 1000),
+            Some(vec![(0, 99), (200, 299)])
+        );
+        assert_eq!(parse("bytes=900-1999", 1000), Some(vec![(900, 999)]));
+    }
+
+    #[test]
+    fn parses_open_ranges() {
+        assert_eq!(parse("bytes=500-", 1000), Some(vec![(500, 999)]));
+        assert_eq!(parse("bytes=0-", 1), Some(vec![(0, 0)]));
+    }
+
+    #[test]
+    fn parses_suffix_ranges() {
+        assert_eq!(parse("bytes=-500", 1000), Some(vec![(500, 999)]));
+        assert_eq!(parse("bytes=-2000", 1000), Some(vec![(0, 999)]));
+    }
+
+    #[test]
+    fn skips_unsatisfiable_ranges() {
+        assert_eq!(parse("bytes=1000-", 1000), Some(vec![]));
+        assert_eq!(parse("bytes=-0", 1000), Some(vec![]));
+        assert_eq!(parse("bytes=-10", 0), Some(vec![]));
+        assert_eq!(parse("bytes=1000-1100, 0-9", 1000), Some(vec![(0, 9)]));
+    }
+
+    #[test]
+    fn serves_whole_content_in_full() {
+        let full_content = |value: &str, total_length: u128| {
+            is_full_content(&parse_ranges(value, total_length).unwrap(), total_length)
+        };
+
+        assert!(full_content("bytes=0-", 1000));
+        assert!(full_content("bytes=0-999", 1000));
+        assert!(full_content("bytes=0-5000", 1000));
+        assert!(full_content("bytes=-1000", 1000));
+        assert!(!full_content("bytes=0-998", 1000));
+        assert!(!full_content("bytes=1-", 1000));
+        assert!(!full_content("bytes=0-499, 500-999", 1000));
+    }
+
+    #[test]
+    fn ignores_invalid_headers() {
+        assert_eq!(parse("items=0-9", 1000), None);
+        assert_eq!(parse("bytes=9-0", 1000), None);
+        assert_eq!(parse("bytes=-", 1000), None);
+        assert_eq!(parse("bytes=a-9", 1000), None);
+        assert_eq!(parse("bytes 0-9", 1000), None);
+    }
+}
@@ -1,96 +1,277 @@
 use crate::memory::STATE;
-use crate::storage::types::state::{Batches, Chunks, StorageRuntimeState};
-use crate::storage::types::store::{Batch, Chunk};
-use ic_cdk::api::time;
+use crate::storage::cert::{
+    build_asset_certificate_headers, build_path_certificate_headers,
+    build_wildcard_certificate_headers, expression_hash, update_certified_data,
+};
+use crate::storage::types::config::{StorageConfigCorsRule, StorageConfigRedirect};
+use crate::storage::types::http::HeaderField;
+use crate::storage::types::state::{
+    AssetHashes, AssetsMigration, FullPath, Hashes, StorageConfigGlobs,
+};
+use crate::storage::types::store::{
+    BatchCompression, BatchDecompression, BatchHash, DeleteAssetsKey,
+};
+use ic_certified_map::Hash;
 
-/// Batch
+// Hashes
 
-pub fn get_batch(batch_id: &u128) -> Option<Batch> {
-    STATE.with(|state| {
-        let batches = state.borrow().runtime.storage.batches.clone();
-        let batch = batches.get(batch_id);
-        batch.cloned()
-    })
+pub fn get_hash(batch_id: &u128) -> Option<BatchHash> {
+    STATE.with(|state| state.borrow().runtime.storage.hashes.get(batch_id).cloned())
 }
 
-pub fn insert_batch(batch_id: &u128, batch: Batch) {
+pub fn insert_hash(batch_id: &u128, hash: BatchHash) {
     STATE.with(|state| {
-        insert_batch_impl(
+        insert_hash_impl(
             batch_id,
-            batch,
-            &mut state.borrow_mut().runtime.storage.batches,
+            hash,
+            &mut state.borrow_mut().runtime.storage.hashes,
         )
     })
 }
 
-pub fn clear_expired_batches() {
-    STATE.with(|state| clear_expired_batches_impl(&mut state.borrow_mut().runtime.storage.batches));
+pub fn delete_hash(batch_id: &u128) {
+    STATE.with(|state| state.borrow_mut().runtime.storage.hashes.remove(batch_id));
+}
+
+fn insert_hash_impl(batch_id: &u128, hash: BatchHash, hashes: &mut Hashes) {
+    hashes.insert(*batch_id, hash);
+}
+
+// Compressions
+
+// The encoder is not cloned, it is taken out of the state while it compresses and inserted back if the compression continues in another call
+pub fn take_compression(batch_id: &u128) -> Option<BatchCompression> {
+    STATE.with(|state| {
+        state
+            .borrow_mut()
+            .runtime
+            .storage
+            .compressions
+            .remove(batch_id)
+    })
+}
+
+pub fn insert_compression(batch_id: &u128, compression: BatchCompression) {
+    STATE.with(|state| {
+        state
+            .borrow_mut()
+            .runtime
+            .storage
+            .compressions
+            .insert(*batch_id, compression)
+    });
+}
+
+// Decompressions
+
+// Likewise the decoder is taken out of the state while it decompresses
+pub fn take_decompression(batch_id: &u128) -> Option<BatchDecompression> {
+    STATE.with(|state| {
+        state
+            .borrow_mut()
+            .runtime
+            .storage
+            .decompressions
+            .remove(batch_id)
+    })
 }
 
-pub fn clear_batch(batch_id: &u128, chunk_ids: &[u128]) {
+pub fn insert_decompression(batch_id: &u128, decompression: BatchDecompression) {
     STATE.with(|state| {
-        clear_batch_impl(batch_id, chunk_ids, &mut state.borrow_mut().runtime.storage)
+        state
+            .borrow_mut()
+            .runtime
+            .storage
+            .decompressions
+            .insert(*batch_id, decompression)
     });
 }
 
-fn insert_batch_impl(batch_id: &u128, batch: Batch, batches: &mut Batches) {
-    batches.insert(*batch_id, batch);
+// Migration
+
+pub fn take_migration() -> Option<AssetsMigration> {
+    STATE.with(|state| state.borrow_mut().runtime.storage.migration.take())
 }
 
-fn clear_expired_batches_impl(batches: &mut Batches) {
-    let now = time();
+pub fn insert_migration(migration: AssetsMigration) {
+    STATE.with(|state| state.borrow_mut().runtime.storage.migration = Some(migration));
+}
 
-    let clone_batches = batches.clone();
+// Delete cursors
 
-    for (batch_id, batch) in clone_batches.iter() {
-        if now > batch.expires_at {
-            batches.remove(batch_id);
-        }
-    }
+// The last full path visited by a deletion that continues in another call
+pub fn take_delete_cursor(key: &DeleteAssetsKey) -> Option<FullPath> {
+    STATE.with(|state| {
+        state
+            .borrow_mut()
+            .runtime
+            .storage
+            .delete_cursors
+            .remove(key)
+    })
 }
 
-fn clear_batch_impl(batch_id: &u128, chunk_ids: &[u128], state: &mut StorageRuntimeState) {
-    for chunk_id in chunk_ids.iter() {
-        state.chunks.remove(chunk_id);
-    }
+pub fn insert_delete_cursor(key: &DeleteAssetsKey, cursor: FullPath) {
+    STATE.with(|state| {
+        state
+            .borrow_mut()
+            .runtime
+            .storage
+            .delete_cursors
+            .insert(key.clone(), cursor)
+    });
+}
 
-    state.batches.remove(batch_id);
+// Certified assets
+
+pub fn insert_asset_hash(path: &FullPath, sha256: Hash) {
+    STATE.with(|state| {
+        state
+            .borrow_mut()
+            .runtime
+            .storage
+            .asset_hashes
+            .insert(path, sha256)
+    });
 }
 
-/// Chunks
+pub fn delete_asset_hash(path: &FullPath) {
+    STATE.with(|state| state.borrow_mut().runtime.storage.asset_hashes.delete(path));
+}
 
-pub fn get_chunk(chunk_id: &u128) -> Option<Chunk> {
+pub fn insert_asset_responses(expr_path: &[String], responses: &[Hash]) {
     STATE.with(|state| {
-        let chunks = state.borrow().runtime.storage.chunks.clone();
-        let chunk = chunks.get(chunk_id);
-        chunk.cloned()
+        state
+            .borrow_mut()
+            .runtime
+            .storage
+            .asset_hashes
+            .insert_responses(expr_path, &expression_hash(), responses)
+    });
+}
+
+pub fn delete_asset_responses(expr_path: &[String]) {
+    STATE.with(|state| {
+        state
+            .borrow_mut()
+            .runtime
+            .storage
+            .asset_hashes
+            .delete_responses(expr_path)
+    });
+}
+
+pub fn reset_asset_hashes() {
+    STATE.with(|state| state.borrow_mut().runtime.storage.asset_hashes = AssetHashes::default());
+}
+
+pub fn update_certified_asset_hashes() {
+    STATE.with(|state| update_certified_data(&state.borrow().runtime.storage.asset_hashes));
+}
+
+pub fn build_certified_asset_headers(
+    url: &str,
+    certificate_version: Option<u16>,
+) -> Result<Vec<HeaderField>, &'static str> {
+    STATE.with(|state| {
+        build_asset_certificate_headers(
+            &state.borrow().runtime.storage.asset_hashes,
+            url,
+            certificate_version,
+        )
     })
 }
 
-pub fn clear_expired_chunks() {
-    STATE.with(|state| clear_expired_chunks_impl(&mut state.borrow_mut().runtime.storage));
+pub fn build_certified_path_headers(
+    url: &str,
+    certificate_version: Option<u16>,
+) -> Result<Vec<HeaderField>, &'static str> {
+    STATE.with(|state| {
+        build_path_certificate_headers(
+            &state.borrow().runtime.storage.asset_hashes,
+            url,
+            certificate_version,
+        )
+    })
 }
 
-pub fn insert_chunk(chunk_id: &u128, chunk: Chunk) {
+pub fn build_certified_wildcard_headers(
+    url: &str,
+    certificate_version: Option<u16>,
+) -> Result<Vec<HeaderField>, &'static str> {
     STATE.with(|state| {
-        insert_chunk_impl(
-            chunk_id,
-            chunk,
-            &mut state.borrow_mut().runtime.storage.chunks,
+        build_wildcard_certificate_headers(
+            &state.borrow().runtime.storage.asset_hashes,
+            url,
+            certificate_version,
         )
     })
 }
 
-fn clear_expired_chunks_impl(state: &mut StorageRuntimeState) {
-    let cloned_chunks = state.chunks.clone();
+// Config
 
-    for (chunk_id, chunk) in cloned_chunks.iter() {
-        if state.batches.get(&chunk.batch_id).is_none() {
-            state.chunks.remove(chunk_id);
-        }
-    }
+pub fn set_config_globs(globs: StorageConfigGlobs) {
+    STATE.with(|state| state.borrow_mut().runtime.storage.config = globs);
 }
 
-fn insert_chunk_impl(chunk_id: &u128, chunk: Chunk, chunks: &mut Chunks) {
-    chunks.insert(*chunk_id, chunk);
+// The destination of the most specific rewrite matching the path
+pub fn get_config_rewrite(path: &str) -> Option<FullPath> {
+    STATE.with(|state| {
+        state
+            .borrow()
+            .runtime
+            .storage
+            .config
+            .rewrites
+            .matches(path)
+            .last()
+            .map(|destination| destination.to_string())
+    })
+}
+
+// The redirect of the most specific pattern matching the path
+pub fn get_config_redirect(path: &str) -> Option<StorageConfigRedirect> {
+    STATE.with(|state| {
+        state
+            .borrow()
+            .runtime
+            .storage
+            .config
+            .redirects
+            .matches(path)
+            .last()
+            .map(|redirect| (*redirect).clone())
+    })
+}
+
+// The CORS rule of the most specific pattern matching the path
+pub fn get_config_cors(path: &str) -> Option<StorageConfigCorsRule> {
+    STATE.with(|state| {
+        state
+            .borrow()
+            .runtime
+            .storage
+            .config
+            .cors
+            .matches(path)
+            .last()
+            .map(|cors| (*cors).clone())
+    })
+}
+
+// The headers of the patterns matching the path, the ones of the most specific pattern last
+pub fn get_config_headers(path: &str) -> Vec<HeaderField> {
+    STATE.with(|state| {
+        state
+            .borrow()
+            .runtime
+            .storage
+            .config
+            .headers
+            .matches(path)
+            .into_iter()
+            .flatten()
+            .cloned()
+            .collect()
+    })
 }
@@ -1,8 +1,18 @@
 use crate::memory::STATE;
-use crate::storage::types::state::{AssetsStable, FullPath, StableFullPath};
-use crate::storage::types::store::Asset;
+use crate::shared::api::time;
+use crate::storage::types::config::StorageConfig;
+use crate::storage::types::state::{
+    AssetsStable, BatchesStable, BlobsStable, ChunksStable, CommitsStable, ContentChunksStable,
+    FullPath, StableBlob, StableCollectionAssetKey, StableEncodingChunkKey, StableFullPath,
+    StableUploadIds, StorageConfigStable, UploadIdsStable, WritePolicyStable,
+};
+use crate::storage::types::store::{Asset, AssetKey, Batch, BatchCommit, Chunk, WritePolicy};
+use crate::types::core::{Blob, CollectionKey};
+use crate::types::state::StableState;
+use ic_certified_map::Hash;
+use std::ops::Bound;
 
-/// Assets
+// Assets
 
 pub fn get_public_asset(full_path: &FullPath) -> Option<Asset> {
     STATE.with(|state| get_asset_stable(full_path, &state.borrow().stable.assets))
@@ -13,7 +23,24 @@ pub fn get_asset(full_path: &FullPath) -> Option<Asset> {
 }
 
 pub fn insert_asset(full_path: &FullPath, asset: &Asset) {
-    STATE.with(|state| insert_asset_stable(full_path, asset, &mut state.borrow_mut().stable.assets))
+    STATE.with(|state| insert_asset_stable(full_path, asset, &mut state.borrow_mut().stable))
+}
+
+pub fn delete_asset(full_path: &FullPath) -> Option<Asset> {
+    STATE.with(|state| delete_asset_stable(full_path, &mut state.borrow_mut().stable))
+}
+
+// Add an asset saved before the assets of the collections were indexed. Returns its collection.
+pub fn index_asset(full_path: &FullPath) -> Option<CollectionKey> {
+    STATE.with(|state| {
+        let stable = &mut state.borrow_mut().stable;
+
+        let asset = get_asset_stable(full_path, &stable.assets)?;
+
+        index_asset_stable(&asset.key, stable);
+
+        Some(asset.key.collection)
+    })
 }
 
 // Get
@@ -24,8 +51,133 @@ fn get_asset_stable(full_path: &FullPath, assets: &AssetsStable) -> Option<Asset
 
 // Insert
 
-fn insert_asset_stable(full_path: &FullPath, asset: &Asset, assets: &mut AssetsStable) {
-    assets.insert(stable_full_path(full_path), asset.clone());
+fn insert_asset_stable(full_path: &FullPath, asset: &Asset, stable: &mut StableState) {
+    let previous = stable
+        .assets
+        .insert(stable_full_path(full_path), asset.clone());
+
+    if let Some(previous) =
+        previous.filter(|previous| previous.key.collection != asset.key.collection)
+    {
+        unindex_asset_stable(&previous.key, stable);
+    }
+
+    index_asset_stable(&asset.key, stable);
+}
+
+// Delete
+
+fn delete_asset_stable(full_path: &FullPath, stable: &mut StableState) -> Option<Asset> {
+    let asset = stable.assets.remove(&stable_full_path(full_path));
+
+    if let Some(asset) = &asset {
+        unindex_asset_stable(&asset.key, stable);
+    }
+
+    asset
+}
+
+// An asset is counted once in its collection, whether it is inserted again or indexed by the migration
+fn index_asset_stable(key: &AssetKey, stable: &mut StableState) {
+    let collection_asset_key = stable_collection_asset_key(&key.collection, &key.full_path);
+
+    if stable
+        .collection_assets
+        .insert(collection_asset_key, ())
+        .is_none()
+    {
+        let count = stable.collection_counts.get(&key.collection).unwrap_or(0);
+
+        stable
+            .collection_counts
+            .insert(key.collection.clone(), count + 1);
+    }
+}
+
+// Likewise an asset that was not indexed is not uncounted
+fn unindex_asset_stable(key: &AssetKey, stable: &mut StableState) {
+    let collection_asset_key = stable_collection_asset_key(&key.collection, &key.full_path);
+
+    if stable
+        .collection_assets
+        .remove(&collection_asset_key)
+        .is_none()
+    {
+        return;
+    }
+
+    match stable.collection_counts.get(&key.collection).unwrap_or(0) {
+        0 | 1 => stable.collection_counts.remove(&key.collection),
+        count => stable
+            .collection_counts
+            .insert(key.collection.clone(), count - 1),
+    };
+}
+
+// The next asset of the collection - optionally whose full path starts with the prefix - after the cursor. The assets of the collection are ordered by full path, the search starts at the cursor or the prefix instead of the first one.
+pub fn get_next_asset(
+    collection: &CollectionKey,
+    prefix: &Option<FullPath>,
+    cursor: &Option<FullPath>,
+) -> Option<Asset> {
+    STATE.with(|state| get_next_asset_stable(collection, prefix, cursor, &state.borrow().stable))
+}
+
+pub fn get_assets(collection: &CollectionKey) -> Vec<Asset> {
+    STATE.with(|state| get_assets_stable(collection, &state.borrow().stable))
+}
+
+pub fn count_assets(collection: &CollectionKey) -> usize {
+    STATE.with(|state| count_assets_stable(collection, &state.borrow().stable))
+}
+
+pub fn get_full_paths() -> Vec<FullPath> {
+    STATE.with(|state| get_full_paths_stable(&state.borrow().stable.assets))
+}
+
+// The assets reference their content, the chunks are not loaded
+fn get_assets_stable(collection: &CollectionKey, stable: &StableState) -> Vec<Asset> {
+    stable
+        .collection_assets
+        .range(stable_collection_asset_key(collection, &FullPath::new())..)
+        .take_while(|(key, _)| &key.collection == collection)
+        .filter_map(|(key, _)| get_asset_stable(&key.full_path, &stable.assets))
+        .collect()
+}
+
+fn get_next_asset_stable(
+    collection: &CollectionKey,
+    prefix: &Option<FullPath>,
+    cursor: &Option<FullPath>,
+    stable: &StableState,
+) -> Option<Asset> {
+    let start = match (cursor, prefix) {
+        (Some(cursor), _) => Bound::Excluded(stable_collection_asset_key(collection, cursor)),
+        (None, Some(prefix)) => Bound::Included(stable_collection_asset_key(collection, prefix)),
+        (None, None) => Bound::Included(stable_collection_asset_key(collection, &FullPath::new())),
+    };
+
+    stable
+        .collection_assets
+        .range((start, Bound::Unbounded))
+        .take_while(|(key, _)| {
+            &key.collection == collection
+                && prefix
+                    .as_ref()
+                    .is_none_or(|prefix| key.full_path.starts_with(prefix))
+        })
+        .find_map(|(key, _)| get_asset_stable(&key.full_path, &stable.assets))
+}
+
+fn count_assets_stable(collection: &CollectionKey, stable: &StableState) -> usize {
+    stable
+        .collection_counts
+        .get(collection)
+        .map_or(0, |count| usize::try_from(count).unwrap())
+}
+
+fn get_full_paths_stable(assets: &AssetsStable) -> Vec<FullPath> {
+    assets.iter().map(|(key, _)| key.full_path).collect()
 }
 
 fn stable_full_path(full_path: &FullPath) -> StableFullPath {
@@ -33,3 +185,366 @@ fn stable_full_path(full_path: &FullPath) -> StableFullPath {
         full_path: full_path.clone(),
     }
 }
+
+fn stable_collection_asset_key(
+    collection: &CollectionKey,
+    full_path: &FullPath,
+) -> StableCollectionAssetKey {
+    StableCollectionAssetKey {
+        collection: collection.clone(),
+        full_path: full_path.clone(),
+    }
+}
+
+// Content chunks
+
+pub fn get_content_chunk(key: &StableEncodingChunkKey) -> Option<Blob> {
+    STATE.with(|state| get_content_chunk_stable(key, &state.borrow().stable.content_chunks))
+}
+
+pub fn insert_content_chunk(key: &StableEncodingChunkKey, content: &Blob) {
+    STATE.with(|state| {
+        insert_content_chunk_stable(key, content, &mut state.borrow_mut().stable.content_chunks)
+    })
+}
+
+pub fn delete_content_chunks(keys: &[StableEncodingChunkKey]) {
+    STATE.with(|state| {
+        delete_content_chunks_stable(keys, &mut state.borrow_mut().stable.content_chunks)
+    })
+}
+
+fn get_content_chunk_stable(
+    key: &StableEncodingChunkKey,
+    content_chunks: &ContentChunksStable,
+) -> Option<Blob> {
+    content_chunks.get(key)
+}
+
+fn insert_content_chunk_stable(
+    key: &StableEncodingChunkKey,
+    content: &Blob,
+    content_chunks: &mut ContentChunksStable,
+) {
+    content_chunks.insert(key.clone(), content.clone());
+}
+
+fn delete_content_chunks_stable(
+    keys: &[StableEncodingChunkKey],
+    content_chunks: &mut ContentChunksStable,
+) {
+    for key in keys.iter() {
+        content_chunks.remove(key);
+    }
+}
+
+// Blobs
+
+pub fn get_blob(sha256: &Hash) -> Option<StableBlob> {
+    STATE.with(|state| get_blob_stable(sha256, &state.borrow().stable.blobs))
+}
+
+pub fn insert_blob(sha256: &Hash, blob: &StableBlob) {
+    STATE.with(|state| insert_blob_stable(sha256, blob, &mut state.borrow_mut().stable.blobs))
+}
+
+pub fn delete_blob(sha256: &Hash) {
+    STATE.with(|state| delete_blob_stable(sha256, &mut state.borrow_mut().stable.blobs))
+}
+
+fn get_blob_stable(sha256: &Hash, blobs: &BlobsStable) -> Option<StableBlob> {
+    blobs.get(sha256)
+}
+
+fn insert_blob_stable(sha256: &Hash, blob: &StableBlob, blobs: &mut BlobsStable) {
+    blobs.insert(*sha256, blob.clone());
+}
+
+fn delete_blob_stable(sha256: &Hash, blobs: &mut BlobsStable) {
+    blobs.remove(sha256);
+}
+
+// Batches
+
+pub fn get_batch(batch_id: &u128) -> Option<Batch> {
+    STATE.with(|state| get_batch_stable(batch_id, &state.borrow().stable.batches))
+}
+
+pub fn insert_batch(batch_id: &u128, batch: &Batch) {
+    STATE.with(|state| insert_batch_stable(batch_id, batch, &mut state.borrow_mut().stable.batches))
+}
+
+pub fn delete_batch(batch_id: &u128) {
+    STATE.with(|state| delete_batch_stable(batch_id, &mut state.borrow_mut().stable.batches))
+}
+
+pub fn clear_expired_batches() -> Vec<u128> {
+    STATE.with(|state| clear_expired_batches_stable(&mut state.borrow_mut().stable.batches))
+}
+
+fn get_batch_stable(batch_id: &u128, batches: &BatchesStable) -> Option<Batch> {
+    batches.get(batch_id)
+}
+
+fn insert_batch_stable(batch_id: &u128, batch: &Batch, batches: &mut BatchesStable) {
+    batches.insert(*batch_id, batch.clone());
+}
+
+fn delete_batch_stable(batch_id: &u128, batches: &mut BatchesStable) {
+    batches.remove(batch_id);
+}
+
+fn clear_expired_batches_stable(batches: &mut BatchesStable) -> Vec<u128> {
+    let now = time();
+
+    let expired_batch_ids: Vec<u128> = batches
+        .iter()
+        .filter(|(_, batch)| now > batch.expires_at)
+        .map(|(batch_id, _)| batch_id)
+        .collect();
+
+    for batch_id in expired_batch_ids.iter() {
+        batches.remove(batch_id);
+    }
+
+    expired_batch_ids
+}
+
+// Chunks
+
+pub fn get_chunk(batch_id: &u128, chunk_id: &u128) -> Option<Chunk> {
+    STATE.with(|state| get_chunk_stable(batch_id, chunk_id, &state.borrow().stable.chunks))
+}
+
+pub fn insert_chunk(chunk_id: &u128, chunk: &Chunk) {
+    STATE.with(|state| insert_chunk_stable(chunk_id, chunk, &mut state.borrow_mut().stable.chunks))
+}
+
+pub fn delete_chunks(batch_id: &u128) {
+    STATE.with(|state| delete_chunks_stable(batch_id, &mut state.borrow_mut().stable.chunks))
+}
+
+fn get_chunk_stable(batch_id: &u128, chunk_id: &u128, chunks: &ChunksStable) -> Option<Chunk> {
+    chunks.get(&(*batch_id, *chunk_id))
+}
+
+fn insert_chunk_stable(chunk_id: &u128, chunk: &Chunk, chunks: &mut ChunksStable) {
+    chunks.insert((chunk.batch_id, *chunk_id), chunk.clone());
+}
+
+fn delete_chunks_stable(batch_id: &u128, chunks: &mut ChunksStable) {
+    let keys: Vec<(u128, u128)> = chunks
+        .range((*batch_id, u128::MIN)..=(*batch_id, u128::MAX))
+        .map(|(key, _)| key)
+        .collect();
+
+    for key in keys.iter() {
+        chunks.remove(key);
+    }
+}
+
+// Commits
+
+pub fn get_commit(batch_id: &u128) -> Option<BatchCommit> {
+    STATE.with(|state| get_commit_stable(batch_id, &state.borrow().stable.commits))
+}
+
+pub fn insert_commit(batch_id: &u128, commit: &BatchCommit) {
+    STATE.with(|state| {
+        insert_commit_stable(batch_id, commit, &mut state.borrow_mut().stable.commits)
+    })
+}
+
+pub fn delete_commit(batch_id: &u128) -> Option<BatchCommit> {
+    STATE.with(|state| delete_commit_stable(batch_id, &mut state.borrow_mut().stable.commits))
+}
+
+fn get_commit_stable(batch_id: &u128, commits: &CommitsStable) -> Option<BatchCommit> {
+    commits.get(batch_id)
+}
+
+fn insert_commit_stable(batch_id: &u128, commit: &BatchCommit, commits: &mut CommitsStable) {
+    commits.insert(*batch_id, commit.clone());
+}
+
+fn delete_commit_stable(batch_id: &u128, commits: &mut CommitsStable) -> Option<BatchCommit> {
+    commits.remove(batch_id)
+}
+
+// Upload ids
+
+pub fn next_batch_id() -> u128 {
+    STATE.with(|state| next_batch_id_stable(&mut state.borrow_mut().stable.upload_ids))
+}
+
+pub fn next_chunk_id() -> u128 {
+    STATE.with(|state| next_chunk_id_stable(&mut state.borrow_mut().stable.upload_ids))
+}
+
+fn next_batch_id_stable(upload_ids: &mut UploadIdsStable) -> u128 {
+    let ids = upload_ids.get().clone();
+
+    let batch_id = ids.batch_id + 1;
+
+    set_upload_ids_stable(StableUploadIds { batch_id, ..ids }, upload_ids);
+
+    batch_id
+}
+
+fn next_chunk_id_stable(upload_ids: &mut UploadIdsStable) -> u128 {
+    let ids = upload_ids.get().clone();
+
+    let chunk_id = ids.chunk_id + 1;
+
+    set_upload_ids_stable(StableUploadIds { chunk_id, ..ids }, upload_ids);
+
+    chunk_id
+}
+
+fn set_upload_ids_stable(ids: StableUploadIds, upload_ids: &mut UploadIdsStable) {
+    upload_ids.set(ids).expect("Failed to save the upload ids");
+}
+
+// Write policy
+
+pub fn get_write_policy() -> WritePolicy {
+    STATE.with(|state| get_write_policy_stable(&state.borrow().stable.write_policy))
+}
+
+pub fn set_write_policy(policy: &WritePolicy) {
+    STATE.with(|state| set_write_policy_stable(policy, &mut state.borrow_mut().stable.write_policy))
+}
+
+fn get_write_policy_stable(write_policy: &WritePolicyStable) -> WritePolicy {
+    write_policy.get().clone()
+}
+
+fn set_write_policy_stable(policy: &WritePolicy, write_policy: &mut WritePolicyStable) {
+    write_policy
+        .set(policy.clone())
+        .expect("Failed to save the write policy");
+}
+
+// Config
+
+pub fn get_config() -> StorageConfig {
+    STATE.with(|state| get_config_stable(&state.borrow().stable.storage_config))
+}
+
+pub fn set_config(config: &StorageConfig) {
+    STATE.with(|state| set_config_stable(config, &mut state.borrow_mut().stable.storage_config))
+}
+
+fn get_config_stable(storage_config: &StorageConfigStable) -> StorageConfig {
+    storage_config.get().clone()
+}
+
+fn set_config_stable(config: &StorageConfig, storage_config: &mut StorageConfigStable) {
+    storage_config
+        .set(config.clone())
+        .expect("Failed to save the storage config");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candid::Principal;
+    use std::collections::HashMap;
+
+    fn asset(collection: &str, full_path: &str) -> Asset {
+        Asset {
+            key: AssetKey {
+                name: full_path.rsplit('/').next().unwrap().to_string(),
+                full_path: full_path.to_string(),
+                token: None,
+                collection: collection.to_string(),
+                owner: Principal::anonymous(),
+                description: None,
+            },
+            headers: vec![],
+            encodings: HashMap::new(),
+            created_at: 0,
+            updated_at: 0,
+        }
+    }
+
+    fn full_paths(collection: &str) -> Vec<FullPath> {
+        get_assets(&collection.to_string())
+            .into_iter()
+            .map(|asset| asset.key.full_path)
+            .collect()
+    }
+
+    #[test]
+    fn counts_the_assets_of_each_collection() {
+        insert_asset(
+            &"/images/b.png".to_string(),
+            &asset("images", "/images/b.png"),
+        );
+        insert_asset(
+            &"/images/a.png".to_string(),
+            &asset("images", "/images/a.png"),
+        );
+        insert_asset(&"/docs/a.pdf".to_string(), &asset("docs", "/docs/a.pdf"));
+
+        // Replacing an asset does not count it twice
+        insert_asset(
+            &"/images/a.png".to_string(),
+            &asset("images", "/images/a.png"),
+        );
+
+        assert_eq!(count_assets(&"images".to_string()), 2);
+        assert_eq!(count_assets(&"docs".to_string()), 1);
+        assert_eq!(full_paths("images"), vec!["/images/a.png", "/images/b.png"]);
+
+        // Moving an asset to another collection
+        insert_asset(
+            &"/images/b.png".to_string(),
+            &asset("docs", "/images/b.png"),
+        );
+
+        assert_eq!(count_assets(&"images".to_string()), 1);
+        assert_eq!(count_assets(&"docs".to_string()), 2);
+        assert_eq!(full_paths("docs"), vec!["/docs/a.pdf", "/images/b.png"]);
+
+        delete_asset(&"/images/a.png".to_string());
+        delete_asset(&"/images/a.png".to_string());
+
+        assert_eq!(count_assets(&"images".to_string()), 0);
+        assert!(full_paths("images").is_empty());
+
+        // The migration indexes an asset once
+        assert_eq!(
+            index_asset(&"/docs/a.pdf".to_string()),
+            Some("docs".to_string())
+        );
+        assert_eq!(index_asset(&"/images/a.png".to_string()), None);
+        assert_eq!(count_assets(&"docs".to_string()), 2);
+    }
+
+    #[test]
+    fn finds_the_next_asset_of_a_collection_by_prefix() {
+        for full_path in ["/images/a/1.png", "/images/a/2.png", "/images/b/1.png"] {
+            insert_asset(&full_path.to_string(), &asset("images", full_path));
+        }
+        insert_asset(
+            &"/images/a/3.png".to_string(),
+            &asset("docs", "/images/a/3.png"),
+        );
+
+        let collection = "images".to_string();
+        let prefix = Some("/images/a/".to_string());
+
+        let next = |cursor: Option<&str>| {
+            get_next_asset(&collection, &prefix, &cursor.map(str::to_string))
+                .map(|asset| asset.key.full_path)
+        };
+
+        assert_eq!(next(None), Some("/images/a/1.png".to_string()));
+        assert_eq!(
+            next(Some("/images/a/1.png")),
+            Some("/images/a/2.png".to_string())
+        );
+        assert_eq!(next(Some("/images/a/2.png")), None);
+    }
+}
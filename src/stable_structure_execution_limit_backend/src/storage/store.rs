@@ -1,29 +1,85 @@
-use crate::msg::ERROR_CANNOT_COMMIT_BATCH;
+use crate::admins::store::is_admin;
+use crate::msg::{
+    ERROR_CANNOT_COMMIT_BATCH, ERROR_CANNOT_DELETE_ASSET, ERROR_CANNOT_OVERWRITE_ASSET,
+    ERROR_COLLECTION_MISMATCH, ERROR_MAX_ITEMS_REACHED, ERROR_MAX_SIZE_EXCEEDED,
+    ERROR_PERMISSION_DENIED,
+};
+use crate::rules::assert::{assert_create_rule, assert_rule, public_permission};
+use crate::rules::store::get_rule;
+use crate::rules::types::rules::Rule;
+use crate::shared::api::{instruction_counter, time};
+use crate::shared::list::{list_values, matcher_regex};
 use crate::shared::utils::principal_not_equal;
+use crate::types::core::{Blob, CollectionKey};
+use crate::types::list::{ListParams, ListResults};
 use candid::Principal;
-use ic_cdk::api::time;
-use std::collections::HashMap;
-
-use crate::storage::constants::{ASSET_ENCODING_NO_COMPRESSION, ENCODING_CERTIFICATION_ORDER};
+use ic_certified_map::Hash;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeSet, HashMap};
+use url::Url;
+
+use crate::storage::cert::{exact_expr_path, response_hash, sha256, wildcard_expr_path};
+use crate::storage::constants::{
+    ASSET_ENCODING_NO_COMPRESSION, COMPRESSED_CHUNK_LENGTH, COMPRESSION_ENCODINGS,
+    DECOMPRESSION_ENCODINGS, ENCODING_CERTIFICATION_ORDER, HTTP_METHODS, MAX_DECOMPRESSED_LENGTH,
+    REDIRECT_STATUS_CODES,
+};
+use crate::storage::http::{
+    build_asset_headers, content_length_header, uncertified_not_found_response,
+    uncertified_options_response, uncertified_redirect_response,
+};
+use crate::storage::migration::migrate_assets as migrate_state_assets;
 use crate::storage::runtime::{
-    clear_batch as clear_runtime_batch, clear_expired_batches as clear_expired_runtime_batches,
-    clear_expired_chunks as clear_expired_runtime_chunks, get_batch as get_runtime_batch,
-    get_chunk as get_runtime_chunk, insert_batch as insert_runtime_batch,
-    insert_chunk as insert_runtime_chunk,
+    delete_asset_hash as delete_runtime_asset_hash,
+    delete_asset_responses as delete_runtime_asset_responses, delete_hash as delete_runtime_hash,
+    get_config_cors as get_runtime_config_cors, get_config_headers as get_runtime_config_headers,
+    get_config_redirect as get_runtime_config_redirect,
+    get_config_rewrite as get_runtime_config_rewrite, get_hash as get_runtime_hash,
+    insert_asset_hash as insert_runtime_asset_hash,
+    insert_asset_responses as insert_runtime_asset_responses,
+    insert_compression as insert_runtime_compression,
+    insert_decompression as insert_runtime_decompression,
+    insert_delete_cursor as insert_runtime_delete_cursor, insert_hash as insert_runtime_hash,
+    reset_asset_hashes as reset_runtime_asset_hashes, set_config_globs as set_runtime_config_globs,
+    take_compression as take_runtime_compression, take_decompression as take_runtime_decompression,
+    take_delete_cursor as take_runtime_delete_cursor,
+    update_certified_asset_hashes as update_runtime_certified_asset_hashes,
 };
 use crate::storage::state::{
-    get_asset as get_state_asset, get_public_asset as get_state_public_asset,
-    insert_asset as insert_state_asset,
+    clear_expired_batches as clear_expired_state_batches, count_assets as count_state_assets,
+    delete_asset as delete_state_asset, delete_batch as delete_state_batch,
+    delete_blob as delete_state_blob, delete_chunks as delete_state_chunks,
+    delete_commit as delete_state_commit, delete_content_chunks as delete_state_content_chunks,
+    get_asset as get_state_asset, get_assets as get_state_assets, get_batch as get_state_batch,
+    get_blob as get_state_blob, get_chunk as get_state_chunk, get_commit as get_state_commit,
+    get_config as get_state_config, get_content_chunk as get_state_content_chunk,
+    get_full_paths as get_state_full_paths, get_next_asset as get_state_next_asset,
+    get_public_asset as get_state_public_asset, get_write_policy as get_state_write_policy,
+    insert_asset as insert_state_asset, insert_batch as insert_state_batch,
+    insert_blob as insert_state_blob, insert_chunk as insert_state_chunk,
+    insert_commit as insert_state_commit, insert_content_chunk as insert_state_content_chunk,
+    next_batch_id as next_state_batch_id, next_chunk_id as next_state_chunk_id,
+    set_config as set_state_config, set_write_policy as set_state_write_policy,
 };
+use crate::storage::types::config::{StorageConfig, StorageConfigRedirect};
+use crate::storage::types::http::{HeaderField, HttpResponse};
 use crate::storage::types::http_request::{MapUrl, PublicAsset};
-use crate::storage::types::interface::{CommitBatch, InitAssetKey, UploadChunk};
-use crate::storage::types::state::FullPath;
-use crate::storage::types::store::{Asset, AssetEncoding, AssetKey, Batch, Chunk};
-use crate::storage::url::{map_alternative_paths, map_url};
+use crate::storage::types::interface::{
+    AssetNoContent, CommitBatch, CommitBatchResult, DeleteAssetsResult, InitAssetKey,
+    MigrateAssetsResult, UploadChunk,
+};
+use crate::storage::types::state::{
+    FullPath, StableBlob, StableEncodingChunkKey, StorageConfigGlobs,
+};
+use crate::storage::types::store::{
+    Asset, AssetEncoding, AssetKey, Batch, BatchCommit, BatchCompression, BatchDecompression,
+    BatchHash, Chunk, ContentDecoder, ContentEncoder, DeleteAssetsKey, WritePolicy,
+};
+use crate::storage::url::{aliased_by, glob_prefix, map_alternative_paths, map_url};
 
-///
-/// Getter, list and delete
-///
+//
+// Getter, list and delete
+//
 
 pub fn get_public_asset_for_url(url: String) -> Result<PublicAsset, &'static str> {
     if url.is_empty() {
@@ -50,7 +106,11 @@ pub fn get_public_asset_for_url(url: String) -> Result<PublicAsset, &'static str
         match asset {
             None => (),
             Some(_) => {
-                return Ok(PublicAsset { url: path, asset });
+                return Ok(PublicAsset {
+                    url: path,
+                    asset,
+                    rewritten: false,
+                });
             }
         }
     }
@@ -61,18 +121,43 @@ pub fn get_public_asset_for_url(url: String) -> Result<PublicAsset, &'static str
     match asset {
         None => (),
         Some(_) => {
-            return Ok(PublicAsset { url: path, asset });
+            return Ok(PublicAsset {
+                url: path,
+                asset,
+                rewritten: false,
+            });
+        }
+    }
+
+    // The paths without asset are rewritten, e.g. /** -> /index.html for a single-page application
+    if let Some(destination) = get_runtime_config_rewrite(&path) {
+        let asset: Option<Asset> = get_public_asset(destination, token);
+
+        if asset.is_some() {
+            return Ok(PublicAsset {
+                url: path,
+                asset,
+                rewritten: true,
+            });
         }
     }
 
     Ok(PublicAsset {
         url: path,
         asset: None,
+        rewritten: false,
     })
 }
 
+// The redirect of the path of the url, evaluated before any asset is looked up
+pub fn get_redirect_for_url(url: &str) -> Option<(String, StorageConfigRedirect)> {
+    let MapUrl { path, token: _ } = map_url(url).ok()?;
+
+    get_runtime_config_redirect(&path).map(|redirect| (path, redirect))
+}
+
 pub fn get_public_asset(full_path: FullPath, token: Option<String>) -> Option<Asset> {
-    let asset = get_state_public_asset(&full_path);
+    let asset = get_state_public_asset(&full_path).filter(is_public_asset);
 
     match asset {
         None => None,
@@ -83,15 +168,78 @@ pub fn get_public_asset(full_path: FullPath, token: Option<String>) -> Option<As
     }
 }
 
+// The asset configured to be served for an error status code. A page protected by a token is not served.
+pub fn get_error_page(status_code: u16) -> Option<Asset> {
+    let path = get_state_config().error_pages.get(&status_code)?.clone();
+
+    get_public_asset(path, None)
+}
+
+// Only the assets of the collections readable by anyone are served over HTTP
+fn is_public_asset(asset: &Asset) -> bool {
+    get_rule(&asset.key.collection).is_ok_and(|rule| public_permission(&rule.read))
+}
+
+pub fn get_content_chunk(encoding: &AssetEncoding, chunk_index: usize) -> Option<Blob> {
+    encoding
+        .content_chunks
+        .get(chunk_index)
+        .and_then(get_state_content_chunk)
+}
+
+pub fn count_assets(collection: &CollectionKey) -> usize {
+    count_state_assets(collection)
+}
+
+pub fn list_assets(
+    caller: Principal,
+    collection: &CollectionKey,
+    filters: &ListParams,
+) -> Result<ListResults<AssetNoContent>, String> {
+    let rule = get_rule(collection)?;
+
+    let (key_regex, description_regex) = matcher_regex(&filters.matcher)?;
+
+    let matches: Vec<(FullPath, AssetNoContent)> = get_state_assets(collection)
+        .iter()
+        .filter(|asset| assert_rule(&rule.read, asset.key.owner, caller))
+        .filter(|asset| is_listable(caller, asset))
+        .filter(|asset| match filters.owner {
+            None => true,
+            Some(owner) => asset.key.owner == owner,
+        })
+        .filter(|asset| match &key_regex {
+            None => true,
+            Some(regex) => regex.is_match(&asset.key.full_path),
+        })
+        .filter(|asset| match (&description_regex, &asset.key.description) {
+            (None, _) => true,
+            (Some(_), None) => false,
+            (Some(regex), Some(description)) => regex.is_match(description),
+        })
+        .map(|asset| (asset.key.full_path.clone(), AssetNoContent::from(asset)))
+        .collect();
+
+    Ok(list_values(matches, filters))
+}
+
+// The key of a token protected asset contains its token, only its owner can list it
+fn is_listable(caller: Principal, asset: &Asset) -> bool {
+    match &asset.key.token {
+        None => true,
+        Some(_) => !principal_not_equal(caller, asset.key.owner),
+    }
+}
+
 fn get_token_protected_asset(
     asset: &Asset,
-    asset_token: &String,
+    asset_token: &str,
     token: Option<String>,
 ) -> Option<Asset> {
     match token {
         None => None,
         Some(token) => {
-            if &token == asset_token {
+            if token == asset_token {
                 return Some(asset.clone());
             }
 
@@ -100,14 +248,536 @@ fn get_token_protected_asset(
     }
 }
 
-///
-/// Upload batch and chunks
-///
+//
+// Config
+//
+
+pub fn get_config() -> StorageConfig {
+    get_state_config()
+}
+
+pub fn set_config(config: &StorageConfig) -> Result<(), String> {
+    assert_config(config)?;
+
+    let globs = StorageConfigGlobs::new(config)?;
+
+    // The responses of these assets depended on the previous config
+    let previous_full_paths = get_config_full_paths();
+
+    set_state_config(config);
+    set_runtime_config_globs(globs);
+
+    // The headers and the paths of the responses depend on the config. Only the assets matched by its rules - previous or new - and the wildcards are certified again, not all the assets.
+    let full_paths: BTreeSet<FullPath> = previous_full_paths
+        .into_iter()
+        .chain(get_config_full_paths())
+        .collect();
+
+    certify_assets(&full_paths);
+
+    Ok(())
+}
+
+// The assets whose responses depend on the config: their headers, CORS or a redirect served instead of them - for their path or one of its aliases
+fn get_config_full_paths() -> Vec<FullPath> {
+    get_state_full_paths()
+        .into_iter()
+        .filter(|full_path| {
+            [vec![full_path.clone()], aliased_by(full_path)]
+                .concat()
+                .iter()
+                .any(|path| {
+                    !get_runtime_config_headers(path).is_empty()
+                        || get_runtime_config_cors(path).is_some()
+                        || get_runtime_config_redirect(path).is_some()
+                })
+        })
+        .collect()
+}
+
+// The glob patterns are compiled in heap memory and have to be rebuilt after an upgrade
+pub fn init_config_globs() {
+    // The config saved was validated when it was set
+    let globs = StorageConfigGlobs::new(&get_state_config()).unwrap_or_default();
+
+    set_runtime_config_globs(globs);
+}
+
+// The glob patterns are validated when these are compiled
+fn assert_config(
+    StorageConfig {
+        rewrites,
+        redirects,
+        error_pages,
+        cors,
+        ..
+    }: &StorageConfig,
+) -> Result<(), String> {
+    for destination in rewrites.values() {
+        if !destination.starts_with('/') {
+            return Err(format!(
+                "Invalid rewrite destination {}. A destination should be an absolute path.",
+                destination
+            ));
+        }
+    }
+
+    for StorageConfigRedirect {
+        location,
+        status_code,
+    } in redirects.values()
+    {
+        if !REDIRECT_STATUS_CODES.contains(status_code) {
+            return Err(format!(
+                "Invalid redirect status code {}. Supported status codes are 301, 302, 307 and 308.",
+                status_code
+            ));
+        }
+
+        if !location.starts_with('/') && Url::parse(location).is_err() {
+            return Err(format!(
+                "Invalid redirect location {}. A location should be an absolute path or url.",
+                location
+            ));
+        }
+    }
+
+    for (status_code, path) in error_pages.iter() {
+        if !(400..600).contains(status_code) {
+            return Err(format!(
+                "Invalid error page status code {}. An error page is served for a 4xx or 5xx status code.",
+                status_code
+            ));
+        }
+
+        if !path.starts_with('/') {
+            return Err(format!(
+                "Invalid error page {}. A page should be an absolute path.",
+                path
+            ));
+        }
+    }
+
+    for (pattern, cors) in cors.iter() {
+        if cors.allow_origins.is_empty() {
+            return Err(format!(
+                "Invalid CORS rule {}. At least one origin should be allowed.",
+                pattern
+            ));
+        }
+
+        if let Some(method) = cors
+            .allow_methods
+            .iter()
+            .find(|method| !HTTP_METHODS.contains(&method.as_str()))
+        {
+            return Err(format!(
+                "Invalid CORS method {}. Supported methods are {}.",
+                method,
+                HTTP_METHODS.join(", ")
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+//
+// Write policy
+//
+
+pub fn get_write_policy() -> WritePolicy {
+    get_state_write_policy()
+}
+
+pub fn set_write_policy(policy: &WritePolicy) {
+    set_state_write_policy(policy);
+}
+
+fn assert_create_permission(caller: Principal, rule: &Rule) -> Result<(), String> {
+    if !assert_create_rule(&rule.write, caller) {
+        return Err(ERROR_PERMISSION_DENIED.to_string());
+    }
+
+    Ok(())
+}
+
+// The write permission of the collection and the write policy both apply to replace an existing asset
+fn assert_update_permission(caller: Principal, rule: &Rule, current: &Asset) -> Result<(), String> {
+    if !assert_rule(&rule.write, current.key.owner, caller) {
+        return Err(ERROR_PERMISSION_DENIED.to_string());
+    }
+
+    assert_write_policy(caller, current)
+}
+
+// An existing asset is replaced only if the policy allows the caller to write over it
+fn assert_write_policy(caller: Principal, current: &Asset) -> Result<(), String> {
+    let is_owner = !principal_not_equal(caller, current.key.owner);
+
+    let allowed = match get_state_write_policy() {
+        WritePolicy::Owner => is_owner,
+        WritePolicy::Controllers => is_owner || is_admin(caller),
+        WritePolicy::Authenticated => caller != Principal::anonymous(),
+    };
+
+    if !allowed {
+        return Err(ERROR_CANNOT_OVERWRITE_ASSET.to_string());
+    }
+
+    Ok(())
+}
+
+//
+// Delete
+//
+
+// Deleting an asset costs far less than the margin we keep here, the remaining assets are deleted in the next calls
+const DELETE_INSTRUCTIONS_THRESHOLD: u64 = 5_000_000_000;
+
+pub fn delete_asset(
+    caller: Principal,
+    collection: &CollectionKey,
+    full_path: &FullPath,
+) -> Result<Option<Asset>, String> {
+    let asset = get_state_asset(full_path);
+
+    match asset {
+        None => Ok(None),
+        Some(asset) => {
+            if &asset.key.collection != collection {
+                return Err(ERROR_COLLECTION_MISMATCH.to_string());
+            }
+
+            let rule = get_rule(collection)?;
+
+            if !assert_delete_permission(caller, &rule, &asset) {
+                return Err(ERROR_CANNOT_DELETE_ASSET.to_string());
+            }
+
+            delete_asset_impl(&asset);
+
+            certify_wildcards();
+            update_runtime_certified_asset_hashes();
+
+            Ok(Some(asset))
+        }
+    }
+}
+
+// Delete the assets of the collection - optionally only those whose full path starts with the prefix - the caller is allowed to delete.
+// A deletion that reaches the instruction threshold keeps the last full path it visited, the next call of the same caller resumes from there instead of searching the assets from the start again.
+pub fn delete_assets(
+    caller: Principal,
+    collection: &CollectionKey,
+    prefix: &Option<FullPath>,
+) -> Result<DeleteAssetsResult, String> {
+    let rule = get_rule(collection)?;
+
+    let key = DeleteAssetsKey {
+        caller,
+        collection: collection.clone(),
+        prefix: prefix.clone(),
+    };
+
+    let mut cursor: Option<FullPath> = take_runtime_delete_cursor(&key);
+    let mut deleted_assets: usize = 0;
+    let mut interrupted = false;
+
+    while let Some(asset) = get_state_next_asset(collection, prefix, &cursor) {
+        if assert_delete_permission(caller, &rule, &asset) {
+            delete_asset_impl(&asset);
+
+            deleted_assets += 1;
+        }
+
+        cursor = Some(asset.key.full_path);
+
+        if instruction_counter() > DELETE_INSTRUCTIONS_THRESHOLD {
+            interrupted = true;
+            break;
+        }
+    }
+
+    certify_wildcards();
+    update_runtime_certified_asset_hashes();
+
+    if let Some(cursor) = cursor.filter(|_| interrupted) {
+        insert_runtime_delete_cursor(&key, cursor);
+
+        return Ok(DeleteAssetsResult::Continue { deleted_assets });
+    }
+
+    Ok(DeleteAssetsResult::Done { deleted_assets })
+}
+
+// The owner of the asset or an admin, if the write permission of the collection allows them as well
+fn assert_delete_permission(caller: Principal, rule: &Rule, asset: &Asset) -> bool {
+    let is_owner_or_admin = !principal_not_equal(caller, asset.key.owner) || is_admin(caller);
+
+    is_owner_or_admin && assert_rule(&rule.write, asset.key.owner, caller)
+}
+
+// Remove the asset, release its content and its certification. The wildcards and the certified data should be updated afterwards.
+fn delete_asset_impl(asset: &Asset) {
+    delete_state_asset(&asset.key.full_path);
+
+    for encoding in asset.encodings.values() {
+        dereference_blob(encoding);
+    }
+
+    certify_paths(&asset.key.full_path);
+}
+
+//
+// Certification
+//
+
+// Rebuild the certification of all the assets - e.g. after an upgrade or when the configuration of the responses changes
+pub fn init_certified_assets() {
+    reset_runtime_asset_hashes();
+
+    for full_path in get_state_full_paths() {
+        certify_paths(&full_path);
+    }
+
+    certify_wildcards();
+
+    update_runtime_certified_asset_hashes();
+}
+
+// Continue the conversion of the assets saved with a previous format. The assets converted are certified with their content.
+pub fn migrate_assets() -> MigrateAssetsResult {
+    let (full_paths, done) = migrate_state_assets();
+
+    let migrated_assets = full_paths.len();
+
+    certify_assets(&full_paths.into_iter().collect());
+
+    match done {
+        true => MigrateAssetsResult::Done { migrated_assets },
+        false => MigrateAssetsResult::Continue { migrated_assets },
+    }
+}
+
+// Certify again the assets of a collection, e.g. when its read permission changes
+pub fn certify_collection(collection: &CollectionKey) {
+    let full_paths: BTreeSet<FullPath> = get_state_assets(collection)
+        .into_iter()
+        .map(|asset| asset.key.full_path)
+        .collect();
+
+    certify_assets(&full_paths);
+}
+
+fn certify_assets(full_paths: &BTreeSet<FullPath>) {
+    for full_path in full_paths.iter() {
+        certify_paths(full_path);
+    }
+
+    // The assets might be the destinations of rewrites or error pages
+    certify_wildcards();
+
+    update_runtime_certified_asset_hashes();
+}
+
+fn certify_asset(full_path: &FullPath) {
+    certify_assets(&BTreeSet::from([full_path.clone()]));
+}
+
+// An asset is served for its full path and its aliases. The responses certified for each path are the ones of the asset resolved for the path, the same way http_request does.
+fn certify_paths(full_path: &FullPath) {
+    let paths = [vec![full_path.clone()], aliased_by(full_path)].concat();
+
+    for path in paths.iter() {
+        let expr_path = exact_expr_path(path);
+
+        match (get_certified_asset(path), get_runtime_config_redirect(path)) {
+            // The redirect is served instead of the asset, v1 cannot certify it
+            (Some(_), Some(redirect)) => {
+                delete_runtime_asset_hash(path);
+                insert_runtime_asset_responses(
+                    &expr_path,
+                    &[get_redirect_responses(&redirect), get_options_responses()].concat(),
+                );
+            }
+            (Some(asset), None) => {
+                match get_certified_hash(&asset) {
+                    Some(sha256) => insert_runtime_asset_hash(path, sha256),
+                    None => delete_runtime_asset_hash(path),
+                }
+
+                insert_runtime_asset_responses(
+                    &expr_path,
+                    &[
+                        get_certified_responses(path, &asset, 200),
+                        get_options_responses(),
+                    ]
+                    .concat(),
+                );
+            }
+            (None, _) => {
+                delete_runtime_asset_hash(path);
+                delete_runtime_asset_responses(&expr_path);
+            }
+        }
+    }
+}
+
+// The responses served for the paths without asset: a redirect, a rewritten asset or not found - the page of the config or plain text. These are certified for the wildcard of the directory of each redirect and rewrite, and for the fallback.
+// A wildcard certifies the redirects and rewrites of its directory and of its parents, as a path matches the most specific wildcard but possibly a pattern of a parent.
+fn certify_wildcards() {
+    let not_found = [get_not_found_responses(), get_options_responses()].concat();
+
+    let StorageConfig {
+        rewrites,
+        redirects,
+        ..
+    } = get_state_config();
+
+    let rewrites: Vec<(String, FullPath)> = rewrites
+        .into_iter()
+        .map(|(source, destination)| (glob_prefix(&source), destination))
+        .collect();
+
+    let redirects: Vec<(String, StorageConfigRedirect)> = redirects
+        .into_iter()
+        .map(|(source, redirect)| (glob_prefix(&source), redirect))
+        .collect();
+
+    let prefixes: BTreeSet<String> = std::iter::once("/".to_string())
+        .chain(rewrites.iter().map(|(prefix, _)| prefix.clone()))
+        .chain(redirects.iter().map(|(prefix, _)| prefix.clone()))
+        .collect();
+
+    for prefix in prefixes.iter() {
+        let mut responses = not_found.clone();
+
+        for (_, redirect) in redirects
+            .iter()
+            .filter(|(redirect_prefix, _)| prefix.starts_with(redirect_prefix))
+        {
+            responses.extend(get_redirect_responses(redirect));
+        }
+
+        for (_, destination) in rewrites
+            .iter()
+            .filter(|(rewrite_prefix, _)| prefix.starts_with(rewrite_prefix))
+        {
+            if let Some(asset) = get_state_asset(destination).filter(is_public_asset) {
+                responses.extend(get_certified_responses(destination, &asset, 200));
+            }
+        }
+
+        insert_runtime_asset_responses(&wildcard_expr_path(prefix), &responses);
+    }
+}
+
+fn get_certified_asset(path: &FullPath) -> Option<Asset> {
+    map_alternative_paths(path)
+        .iter()
+        .chain(std::iter::once(path))
+        .find_map(|path| get_state_asset(path).filter(is_public_asset))
+}
+
+// Response verification v1 certifies a single content per path. A verifier decodes the body it receives, the hash is the one of the decoded content - i.e. of the identity encoding.
+fn get_certified_hash(asset: &Asset) -> Option<Hash> {
+    asset
+        .encodings
+        .get(ASSET_ENCODING_NO_COMPRESSION)
+        .map(|encoding| encoding.sha256)
+}
+
+// Response verification v2 certifies the response of each encoding. A successful response can also be served without content as not modified.
+fn get_certified_responses(path: &FullPath, asset: &Asset, status_code: u16) -> Vec<Hash> {
+    asset
+        .encodings
+        .iter()
+        .flat_map(|(encoding_type, encoding)| {
+            let headers = build_asset_headers(path, asset, encoding, encoding_type);
+
+            let responses = response_hashes(
+                status_code,
+                &headers,
+                &encoding.sha256,
+                encoding.total_length,
+            );
+
+            match status_code {
+                200 => [responses, vec![response_hash(304, &headers, &sha256(&[]))]].concat(),
+                _ => responses,
+            }
+        })
+        .collect()
+}
+
+// A response and the answer to a HEAD request for it - the same headers with the length of the content, but no content
+fn response_hashes(
+    status_code: u16,
+    headers: &[HeaderField],
+    body_hash: &Hash,
+    content_length: u128,
+) -> Vec<Hash> {
+    vec![
+        response_hash(status_code, headers, body_hash),
+        response_hash(
+            status_code,
+            &[
+                headers.to_vec(),
+                vec![content_length_header(content_length)],
+            ]
+            .concat(),
+            &sha256(&[]),
+        ),
+    ]
+}
+
+// The page of the config is served with the headers of its own path, whatever the path not found
+fn get_not_found_responses() -> Vec<Hash> {
+    let page_responses = get_error_page(404)
+        .map(|page| get_certified_responses(&page.key.full_path, &page, 404))
+        .unwrap_or_default();
+
+    if !page_responses.is_empty() {
+        return page_responses;
+    }
+
+    uncertified_responses(uncertified_not_found_response())
+}
+
+fn get_redirect_responses(redirect: &StorageConfigRedirect) -> Vec<Hash> {
+    uncertified_responses(uncertified_redirect_response(redirect))
+}
+
+// OPTIONS is answered for any path, as long as it is certified
+fn get_options_responses() -> Vec<Hash> {
+    let response = uncertified_options_response();
+
+    vec![response_hash(
+        response.status_code,
+        &response.headers,
+        &sha256(&response.body),
+    )]
+}
+
+fn uncertified_responses(response: HttpResponse) -> Vec<Hash> {
+    response_hashes(
+        response.status_code,
+        &response.headers,
+        &sha256(&response.body),
+        response.body.len() as u128,
+    )
+}
+
+//
+// Upload batch and chunks
+//
 
 const BATCH_EXPIRY_NANOS: u64 = 300_000_000_000;
 
-static mut NEXT_BATCH_ID: u128 = 0;
-static mut NEXT_CHUNK_ID: u128 = 0;
+// The instruction limit of an update message is set per subnet - see the resource limits of the Internet Computer - and is several times this threshold on every subnet.
+// The margin left above the threshold covers the chunk being written when it is crossed and the certification of the asset at the end of the commit.
+const COMMIT_INSTRUCTIONS_THRESHOLD: u64 = 5_000_000_000;
 
 pub fn create_batch(caller: Principal, init: InitAssetKey) -> Result<u128, String> {
     secure_create_batch_impl(caller, init)
@@ -117,7 +787,10 @@ pub fn create_chunk(caller: Principal, chunk: UploadChunk) -> Result<u128, &'sta
     create_chunk_impl(caller, chunk)
 }
 
-pub fn commit_batch(caller: Principal, commit_batch: CommitBatch) -> Result<(), String> {
+pub fn commit_batch(
+    caller: Principal,
+    commit_batch: CommitBatch,
+) -> Result<CommitBatchResult, String> {
     commit_batch_impl(caller, commit_batch)
 }
 
@@ -125,6 +798,20 @@ fn secure_create_batch_impl(caller: Principal, init: InitAssetKey) -> Result<u12
     // Assert supported encoding type
     get_encoding_type(&init.encoding_type)?;
 
+    let rule = get_rule(&init.collection)?;
+
+    // Fail before the upload if the caller cannot write the asset
+    match get_state_asset(&init.full_path) {
+        None => assert_create_permission(caller, &rule)?,
+        Some(current) => {
+            if init.collection != current.key.collection {
+                return Err(ERROR_COLLECTION_MISMATCH.to_string());
+            }
+
+            assert_update_permission(caller, &rule, &current)?;
+        }
+    }
+
     Ok(create_batch_impl(caller, init))
 }
 
@@ -141,31 +828,39 @@ fn create_batch_impl(
 ) -> u128 {
     let now = time();
 
-    unsafe {
-        clear_expired_batches();
+    clear_expired_batches();
 
-        NEXT_BATCH_ID += 1;
+    let batch_id = next_state_batch_id();
 
-        let key: AssetKey = AssetKey {
-            full_path,
-            collection,
-            owner: caller,
-            token,
-            name,
-            description,
-        };
+    let key: AssetKey = AssetKey {
+        full_path,
+        collection,
+        owner: caller,
+        token,
+        name,
+        description,
+    };
 
-        insert_runtime_batch(
-            &NEXT_BATCH_ID,
-            Batch {
-                key,
-                expires_at: now + BATCH_EXPIRY_NANOS,
-                encoding_type,
-            },
-        );
+    insert_state_batch(
+        &batch_id,
+        &Batch {
+            key,
+            expires_at: now + BATCH_EXPIRY_NANOS,
+            encoding_type,
+        },
+    );
+
+    insert_runtime_hash(
+        &batch_id,
+        BatchHash {
+            chunk_ids: vec![],
+            last_order_id: None,
+            total_length: 0,
+            hasher: Sha256::new(),
+        },
+    );
 
-        NEXT_BATCH_ID
-    }
+    batch_id
 }
 
 fn create_chunk_impl(
@@ -176,7 +871,7 @@ fn create_chunk_impl(
         order_id,
     }: UploadChunk,
 ) -> Result<u128, &'static str> {
-    let batch = get_runtime_batch(&batch_id);
+    let batch = get_state_batch(&batch_id);
 
     match batch {
         None => Err("Batch not found."),
@@ -185,45 +880,77 @@ fn create_chunk_impl(
                 return Err("Bach initializer does not match chunk uploader.");
             }
 
+            // The running hash of the batch is used by the commit, the content cannot change anymore
+            if get_state_commit(&batch_id).is_some() {
+                return Err("Batch is being committed. Chunks cannot be added.");
+            }
+
             let now = time();
 
             // Update batch to extend expires_at
-            insert_runtime_batch(
+            insert_state_batch(
                 &batch_id,
-                Batch {
+                &Batch {
                     key: b.key.clone(),
                     expires_at: now + BATCH_EXPIRY_NANOS,
                     encoding_type: b.encoding_type,
                 },
             );
 
-            unsafe {
-                NEXT_CHUNK_ID += 1;
+            let chunk_id = next_state_chunk_id();
 
-                insert_runtime_chunk(
-                    &NEXT_CHUNK_ID,
-                    Chunk {
-                        batch_id,
-                        content,
-                        order_id: order_id.unwrap_or(NEXT_CHUNK_ID),
-                    },
-                );
+            let order_id = order_id.unwrap_or(chunk_id);
 
-                Ok(NEXT_CHUNK_ID)
-            }
+            hash_chunk(&batch_id, &chunk_id, &order_id, &content);
+
+            insert_state_chunk(
+                &chunk_id,
+                &Chunk {
+                    batch_id,
+                    content,
+                    order_id,
+                },
+            );
+
+            Ok(chunk_id)
         }
     }
 }
 
-fn commit_batch_impl(caller: Principal, commit_batch: CommitBatch) -> Result<(), String> {
-    let batch = get_runtime_batch(&commit_batch.batch_id);
+fn hash_chunk(batch_id: &u128, chunk_id: &u128, order_id: &u128, content: &[u8]) {
+    let hash = get_runtime_hash(batch_id);
+
+    // The batch is already hashed at commit time because a previous chunk was received out of order
+    let Some(mut hash) = hash else {
+        return;
+    };
+
+    // A chunk received out of order cannot be appended to the running hash
+    if hash
+        .last_order_id
+        .is_some_and(|last_order_id| *order_id <= last_order_id)
+    {
+        delete_runtime_hash(batch_id);
+        return;
+    }
+
+    hash.hasher.update(content);
+    hash.total_length += u128::try_from(content.len()).unwrap();
+    hash.last_order_id = Some(*order_id);
+    hash.chunk_ids.push(*chunk_id);
+
+    insert_runtime_hash(batch_id, hash);
+}
+
+fn commit_batch_impl(
+    caller: Principal,
+    commit_batch: CommitBatch,
+) -> Result<CommitBatchResult, String> {
+    let batch = get_state_batch(&commit_batch.batch_id);
 
     match batch {
         None => Err(ERROR_CANNOT_COMMIT_BATCH.to_string()),
-        Some(b) => {
-            let _asset = secure_commit_chunks(caller, commit_batch, &b);
-            Ok(())
-        }
+        Some(b) => secure_commit_chunks(caller, commit_batch, &b),
     }
 }
 
@@ -232,34 +959,60 @@ fn secure_commit_chunks(
 
     commit_batch: CommitBatch,
     batch: &Batch,
-) -> Result<Asset, String> {
+) -> Result<CommitBatchResult, String> {
     // The one that started the batch should be the one that commits it
     if principal_not_equal(caller, batch.key.owner) {
         return Err(ERROR_CANNOT_COMMIT_BATCH.to_string());
     }
 
+    // The rule might have changed since the batch was created
+    let rule = get_rule(&batch.key.collection)?;
+
     let current = get_state_asset(&batch.key.full_path);
 
     match current {
-        None => commit_chunks(commit_batch, batch),
-        Some(current) => secure_commit_chunks_update(caller, commit_batch, batch, current),
+        None => secure_commit_chunks_create(caller, commit_batch, batch, &rule),
+        Some(current) => secure_commit_chunks_update(caller, commit_batch, batch, &rule, current),
+    }
+}
+
+fn secure_commit_chunks_create(
+    caller: Principal,
+
+    commit_batch: CommitBatch,
+    batch: &Batch,
+
+    rule: &Rule,
+) -> Result<CommitBatchResult, String> {
+    assert_create_permission(caller, rule)?;
+
+    if let Some(max_items) = rule.max_items {
+        if count_state_assets(&batch.key.collection) >= max_items {
+            return Err(ERROR_MAX_ITEMS_REACHED.to_string());
+        }
     }
+
+    commit_chunks(commit_batch, batch, rule)
 }
 
 fn secure_commit_chunks_update(
-    _caller: Principal,
+    caller: Principal,
 
     commit_batch: CommitBatch,
     batch: &Batch,
 
+    rule: &Rule,
+
     current: Asset,
-) -> Result<Asset, String> {
+) -> Result<CommitBatchResult, String> {
     // The collection of the existing asset should be the same as the one we commit
     if batch.key.collection != current.key.collection {
-        return Err("Provided collection does not match existing collection.".to_string());
+        return Err(ERROR_COLLECTION_MISMATCH.to_string());
     }
 
-    commit_chunks(commit_batch, batch)
+    assert_update_permission(caller, rule, &current)?;
+
+    commit_chunks(commit_batch, batch, rule)
 }
 
 fn commit_chunks(
@@ -267,9 +1020,11 @@ fn commit_chunks(
         chunk_ids,
         batch_id,
         headers,
+        compress,
     }: CommitBatch,
     batch: &Batch,
-) -> Result<Asset, String> {
+    rule: &Rule,
+) -> Result<CommitBatchResult, String> {
     let now = time();
 
     if now > batch.expires_at {
@@ -277,68 +1032,792 @@ fn commit_chunks(
         return Err("Batch did not complete in time. Chunks cannot be committed.".to_string());
     }
 
-    // Collect all chunks
-    let mut chunks: Vec<Chunk> = vec![];
-
-    for chunk_id in chunk_ids.iter() {
-        let chunk = get_runtime_chunk(chunk_id);
+    let encoding_type = get_encoding_type(&batch.encoding_type)?;
 
-        match chunk {
-            None => {
-                return Err("Chunk does not exist.".to_string());
+    let mut commit = match get_state_commit(&batch_id) {
+        None => init_commit(batch_id, &chunk_ids, &rule.max_size)?,
+        Some(commit) => {
+            if commit.chunk_ids.len() != chunk_ids.len()
+                || !chunk_ids.iter().all(|id| commit.chunk_ids.contains(id))
+            {
+                return Err("Chunks do not match the commit in progress.".to_string());
             }
-            Some(c) => {
-                if batch_id != c.batch_id {
-                    return Err("Chunk not included in the provided batch.".to_string());
-                }
 
-                chunks.push(c);
+            // The running hash of a commit is kept in heap memory. If it was lost with an upgrade, the commit is restarted.
+            if commit.sha256.is_none() && get_runtime_hash(&batch_id).is_none() {
+                delete_state_content_chunks(&commit.content_chunks);
+                init_commit(batch_id, &chunk_ids, &rule.max_size)?
+            } else {
+                commit
             }
         }
+    };
+
+    // The asset is already available, the encodings derived from its identity encoding are being compressed
+    if !commit.compressions.is_empty() {
+        return compress_encodings(batch_id, batch, commit);
     }
 
-    // Sort with ordering
-    chunks.sort_by(|a, b| a.order_id.cmp(&b.order_id));
+    // The content is written, its identity encoding is being decompressed
+    if commit.decompression {
+        return decompress_encoding(batch_id, batch, headers, commit);
+    }
 
-    let mut content_chunks: Vec<Vec<u8>> = vec![];
+    let compressions = compression_types(&encoding_type, &compress);
+    let decompression = requires_decompression(&batch.key.full_path, &encoding_type);
+
+    // A content already saved by another asset or encoding does not have to be written again
+    let duplicate = commit
+        .sha256
+        .is_some_and(|sha256| get_state_blob(&sha256).is_some());
+
+    // Write the chunks in stable memory as long as we are not approaching the instruction limit
+    while !duplicate && commit.content_chunks.len() < commit.chunk_ids.len() {
+        if instruction_counter() > COMMIT_INSTRUCTIONS_THRESHOLD {
+            extend_batch(&batch_id, batch);
+
+            return Ok(CommitBatchResult::Continue {
+                committed_chunks: commit.content_chunks.len(),
+                total_chunks: commit.chunk_ids.len()
+                    * (1 + compressions.len() + usize::from(decompression)),
+            });
+        }
+
+        let chunk_index = commit.content_chunks.len();
+
+        let chunk = get_state_chunk(&batch_id, &commit.chunk_ids[chunk_index])
+            .ok_or_else(|| "Chunk does not exist.".to_string())?;
+
+        let key = StableEncodingChunkKey {
+            full_path: batch.key.full_path.clone(),
+            encoding_type: encoding_type.clone(),
+            batch_id,
+            chunk_index,
+        };
+
+        insert_state_content_chunk(&key, &chunk.content);
+
+        if commit.sha256.is_none() {
+            commit.total_length += u128::try_from(chunk.content.len()).unwrap();
+            hash_commit_chunk(&batch_id, &chunk.content)?;
+        }
 
-    // Collect content
-    for c in chunks.iter() {
-        content_chunks.push(c.content.clone());
+        commit.content_chunks.push(key);
+        commit.content_chunk_lengths.push(chunk.content.len());
+
+        // Persist the progress so that the content already written is known if the commit does not complete
+        insert_state_commit(&batch_id, &commit);
     }
 
-    if content_chunks.is_empty() {
-        return Err("No chunk to commit.".to_string());
+    let sha256: Hash = match commit.sha256 {
+        Some(sha256) => sha256,
+        None => get_runtime_hash(&batch_id)
+            .ok_or_else(|| "Commit hash not found.".to_string())?
+            .hasher
+            .finalize()
+            .into(),
+    };
+
+    // The asset is made available with its identity encoding, once it is decompressed
+    if decompression {
+        commit.sha256 = Some(sha256);
+        commit.decompression = true;
+
+        insert_state_commit(&batch_id, &commit);
+
+        return decompress_encoding(batch_id, batch, headers, commit);
+    }
+
+    // All chunks are written, the asset can be made available. The content written is now owned by the blob.
+    let encoding = reference_encoding(
+        &sha256,
+        std::mem::take(&mut commit.content_chunks),
+        std::mem::take(&mut commit.content_chunk_lengths),
+        commit.total_length,
+        false,
+    );
+
+    insert_committed_asset(
+        batch,
+        headers,
+        vec![(encoding_type, encoding)],
+        &compressions,
+    );
+
+    if compressions.is_empty() {
+        clear_batch(&batch_id);
+
+        return Ok(CommitBatchResult::Done);
     }
 
-    let key = batch.clone().key;
+    // The chunks uploaded are not needed anymore, the compression reads the content of the asset
+    delete_state_chunks(&batch_id);
+    delete_runtime_hash(&batch_id);
 
+    commit.sha256 = Some(sha256);
+    commit.compressions = compressions;
+
+    insert_state_commit(&batch_id, &commit);
+
+    compress_encodings(batch_id, batch, commit)
+}
+
+// Update batch to extend expires_at while the commit is in progress
+fn extend_batch(batch_id: &u128, batch: &Batch) {
+    insert_state_batch(
+        batch_id,
+        &Batch {
+            key: batch.key.clone(),
+            expires_at: time() + BATCH_EXPIRY_NANOS,
+            encoding_type: batch.encoding_type.clone(),
+        },
+    );
+}
+
+// Add the committed encodings to the asset - or create it - and certify it. The content of the encodings replaced is not referenced by the asset anymore.
+fn insert_committed_asset(
+    batch: &Batch,
+    headers: Vec<HeaderField>,
+    encodings: Vec<(String, AssetEncoding)>,
+    compressions: &[String],
+) {
     let now = time();
 
     let mut asset: Asset = Asset {
-        key,
+        key: batch.key.clone(),
         headers,
         encodings: HashMap::new(),
         created_at: now,
         updated_at: now,
     };
 
-    if let Some(existing_asset) = get_state_asset(&batch.clone().key.full_path) {
-        asset.encodings = existing_asset.encodings.clone();
+    if let Some(existing_asset) = get_state_asset(&batch.key.full_path) {
+        asset.encodings = existing_asset.encodings;
         asset.created_at = existing_asset.created_at;
     }
 
+    let previous_encodings: Vec<AssetEncoding> = encodings
+        .into_iter()
+        .filter_map(|(encoding_type, encoding)| asset.encodings.insert(encoding_type, encoding))
+        .collect();
+
+    // The other encodings of the asset are replaced by the ones derived from the new identity. These are removed now, as these would otherwise serve the previous content until the compression completes - or forever if it is not worth it.
+    let derived_encodings = take_derived_encodings(&mut asset, compressions);
+
+    insert_state_asset(&batch.key.full_path, &asset);
+
+    certify_asset(&batch.key.full_path);
+
+    for previous_encoding in previous_encodings.iter().chain(derived_encodings.iter()) {
+        dereference_blob(previous_encoding);
+    }
+}
+
+// Only an identity encoding can be compressed
+fn compression_types(encoding_type: &str, compress: &Option<bool>) -> Vec<String> {
+    if encoding_type != ASSET_ENCODING_NO_COMPRESSION || compress != &Some(true) {
+        return vec![];
+    }
+
+    COMPRESSION_ENCODINGS
+        .iter()
+        .map(|encoding_type| encoding_type.to_string())
+        .collect()
+}
+
+//
+// Compression
+//
+
+fn take_derived_encodings(asset: &mut Asset, compressions: &[String]) -> Vec<AssetEncoding> {
+    if compressions.is_empty() {
+        return vec![];
+    }
+
+    let encoding_types: Vec<String> = asset
+        .encodings
+        .keys()
+        .filter(|encoding_type| *encoding_type != ASSET_ENCODING_NO_COMPRESSION)
+        .cloned()
+        .collect();
+
+    encoding_types
+        .iter()
+        .filter_map(|encoding_type| asset.encodings.remove(encoding_type))
+        .collect()
+}
+
+// Compress the identity encoding of the committed asset in each encoding, one after the other, as long as we are not approaching the instruction limit
+fn compress_encodings(
+    batch_id: u128,
+    batch: &Batch,
+    mut commit: BatchCommit,
+) -> Result<CommitBatchResult, String> {
+    // The identity encoding might have been replaced or deleted since it was committed, there is nothing left to compress
+    let source = get_compression_asset(&batch.key.full_path, &commit)
+        .and_then(|asset| asset.encodings.get(ASSET_ENCODING_NO_COMPRESSION).cloned());
+
+    let Some(source) = source else {
+        cancel_compression(&batch_id, &commit);
+        return Ok(CommitBatchResult::Done);
+    };
+
+    let total_chunks = source.content_chunks.len();
+
+    while let Some(encoding_type) = commit.compressions.first().cloned() {
+        // The state of the encoder is kept in heap memory. If it was lost with an upgrade, the compression of the encoding is restarted.
+        let mut compression = match take_runtime_compression(&batch_id) {
+            Some(compression) => compression,
+            None => {
+                delete_state_content_chunks(&commit.compressed_chunks);
+                commit.compressed_chunks = vec![];
+                commit.compressed_chunk_lengths = vec![];
+
+                BatchCompression {
+                    encoder: ContentEncoder::new(&encoding_type)?,
+                    output: vec![],
+                    chunk_index: 0,
+                    total_length: 0,
+                    hasher: Sha256::new(),
+                }
+            }
+        };
+
+        while compression.chunk_index < total_chunks {
+            if instruction_counter() > COMMIT_INSTRUCTIONS_THRESHOLD {
+                let compressed_encodings = COMPRESSION_ENCODINGS.len() - commit.compressions.len();
+
+                let committed_chunks =
+                    total_chunks * (1 + compressed_encodings) + compression.chunk_index;
+
+                insert_runtime_compression(&batch_id, compression);
+                insert_state_commit(&batch_id, &commit);
+                extend_batch(&batch_id, batch);
+
+                return Ok(CommitBatchResult::Continue {
+                    committed_chunks,
+                    total_chunks: total_chunks * (1 + COMPRESSION_ENCODINGS.len()),
+                });
+            }
+
+            let content = get_content_chunk(&source, compression.chunk_index)
+                .ok_or_else(|| "Content to compress does not exist.".to_string())?;
+
+            compression.encoder.write(&content)?;
+            compression.chunk_index += 1;
+
+            let output = compression.encoder.take_output();
+            compression.output.extend(output);
+
+            // The compressed content is written once it fills a chunk
+            while compression.output.len() >= COMPRESSED_CHUNK_LENGTH {
+                let content: Vec<u8> = compression
+                    .output
+                    .drain(..COMPRESSED_CHUNK_LENGTH)
+                    .collect();
+
+                write_compressed_chunk(
+                    batch_id,
+                    batch,
+                    &encoding_type,
+                    &mut commit,
+                    &mut compression.hasher,
+                    &mut compression.total_length,
+                    &content,
+                );
+            }
+
+            // Persist the progress so that the content already written is known if the compression does not complete
+            insert_state_commit(&batch_id, &commit);
+        }
+
+        finish_compression(
+            batch_id,
+            batch,
+            &encoding_type,
+            &mut commit,
+            compression,
+            source.total_length,
+        )?;
+    }
+
+    clear_batch(&batch_id);
+
+    Ok(CommitBatchResult::Done)
+}
+
+// Write the end of the compressed content and add the encoding to the asset. A compressed content that is not smaller than the identity is not worth serving and is dropped.
+fn finish_compression(
+    batch_id: u128,
+    batch: &Batch,
+    encoding_type: &str,
+    commit: &mut BatchCommit,
+    BatchCompression {
+        encoder,
+        mut output,
+        mut total_length,
+        mut hasher,
+        ..
+    }: BatchCompression,
+    source_length: u128,
+) -> Result<(), String> {
+    output.extend(encoder.finish()?);
+
+    for content in output.chunks(COMPRESSED_CHUNK_LENGTH) {
+        write_compressed_chunk(
+            batch_id,
+            batch,
+            encoding_type,
+            commit,
+            &mut hasher,
+            &mut total_length,
+            content,
+        );
+    }
+
+    let compressed_chunks = std::mem::take(&mut commit.compressed_chunks);
+    let compressed_chunk_lengths = std::mem::take(&mut commit.compressed_chunk_lengths);
+
+    commit.compressions.remove(0);
+
+    let asset = get_compression_asset(&batch.key.full_path, commit);
+
+    // A newer upload replaced the identity encoding, the compression of its previous content is abandoned
+    if asset.is_none() {
+        commit.compressions.clear();
+    }
+
+    match asset {
+        Some(mut asset) if total_length < source_length => {
+            let encoding = reference_encoding(
+                &hasher.finalize().into(),
+                compressed_chunks,
+                compressed_chunk_lengths,
+                total_length,
+                false,
+            );
+
+            let previous_encoding = asset.encodings.insert(encoding_type.to_string(), encoding);
+
+            insert_state_asset(&batch.key.full_path, &asset);
+
+            certify_asset(&batch.key.full_path);
+
+            if let Some(previous_encoding) = previous_encoding {
+                dereference_blob(&previous_encoding);
+            }
+        }
+        _ => delete_state_content_chunks(&compressed_chunks),
+    }
+
+    insert_state_commit(&batch_id, commit);
+
+    Ok(())
+}
+
+// The asset whose identity encoding is still the content compressed by the commit
+fn get_compression_asset(full_path: &FullPath, commit: &BatchCommit) -> Option<Asset> {
+    get_state_asset(full_path).filter(|asset| {
+        asset
+            .encodings
+            .get(ASSET_ENCODING_NO_COMPRESSION)
+            .is_some_and(|encoding| commit.sha256 == Some(encoding.sha256))
+    })
+}
+
+fn write_compressed_chunk(
+    batch_id: u128,
+    batch: &Batch,
+    encoding_type: &str,
+    commit: &mut BatchCommit,
+    hasher: &mut Sha256,
+    total_length: &mut u128,
+    content: &[u8],
+) {
+    let key = StableEncodingChunkKey {
+        full_path: batch.key.full_path.clone(),
+        encoding_type: encoding_type.to_string(),
+        batch_id,
+        chunk_index: commit.compressed_chunks.len(),
+    };
+
+    insert_state_content_chunk(&key, &content.to_vec());
+
+    hasher.update(content);
+    *total_length += u128::try_from(content.len()).unwrap();
+
+    commit.compressed_chunks.push(key);
+    commit.compressed_chunk_lengths.push(content.len());
+}
+
+fn cancel_compression(batch_id: &u128, commit: &BatchCommit) {
+    delete_state_content_chunks(&commit.compressed_chunks);
+    clear_batch(batch_id);
+}
+
+//
+// Decompression
+//
+
+// An identity encoding is decompressed from a committed encoding, unless the asset has one that was uploaded
+fn requires_decompression(full_path: &FullPath, encoding_type: &str) -> bool {
+    DECOMPRESSION_ENCODINGS.contains(&encoding_type)
+        && get_state_asset(full_path)
+            .and_then(|asset| asset.encodings.get(ASSET_ENCODING_NO_COMPRESSION).cloned())
+            .is_none_or(|encoding| encoding.decompressed)
+}
+
+// Decompress the committed content in its identity encoding as long as we are not approaching the instruction limit. Both encodings are added to the asset once the content is decompressed.
+// The length of the decompressed content is limited and an invalid content is rejected, the commit is cancelled.
+fn decompress_encoding(
+    batch_id: u128,
+    batch: &Batch,
+    headers: Vec<HeaderField>,
+    mut commit: BatchCommit,
+) -> Result<CommitBatchResult, String> {
+    let encoding_type = get_encoding_type(&batch.encoding_type)?;
+
+    let sha256 = commit
+        .sha256
+        .ok_or_else(|| "Commit hash not found.".to_string())?;
+
+    // The content written by the commit, or the one already saved if it is a duplicate
+    let source = match commit.content_chunks.is_empty() {
+        false => commit.content_chunks.clone(),
+        true => get_state_blob(&sha256)
+            .map(|blob| blob.content_chunks)
+            .ok_or_else(|| "Content to decompress does not exist.".to_string())?,
+    };
+
+    // The same content was already decompressed for another asset
+    if let Some(identity) = reference_decompressed_encoding(&sha256) {
+        delete_state_content_chunks(&commit.compressed_chunks);
+        commit.compressed_chunks = vec![];
+        commit.compressed_chunk_lengths = vec![];
+
+        return finish_decompression(batch_id, batch, headers, commit, identity);
+    }
+
+    let total_chunks = source.len();
+
+    // The state of the decoder is kept in heap memory. If it was lost with an upgrade, the decompression is restarted.
+    let mut decompression = match take_runtime_decompression(&batch_id) {
+        Some(decompression) => decompression,
+        None => {
+            delete_state_content_chunks(&commit.compressed_chunks);
+            commit.compressed_chunks = vec![];
+            commit.compressed_chunk_lengths = vec![];
+
+            BatchDecompression {
+                decoder: ContentDecoder::new(&encoding_type, source)?,
+                total_length: 0,
+                hasher: Sha256::new(),
+            }
+        }
+    };
+
+    loop {
+        if instruction_counter() > COMMIT_INSTRUCTIONS_THRESHOLD {
+            let committed_chunks = total_chunks + decompression.decoder.read_chunks();
+
+            insert_runtime_decompression(&batch_id, decompression);
+            insert_state_commit(&batch_id, &commit);
+            extend_batch(&batch_id, batch);
+
+            return Ok(CommitBatchResult::Continue {
+                committed_chunks,
+                total_chunks: total_chunks * 2,
+            });
+        }
+
+        // The decompressed content is read a chunk at a time, whatever the length of the compressed content it is decompressed from
+        let mut content = vec![0; COMPRESSED_CHUNK_LENGTH];
+
+        let length = match decompression.decoder.read(&mut content) {
+            Ok(length) => length,
+            Err(err) => {
+                cancel_decompression(&batch_id, &commit);
+                return Err(err);
+            }
+        };
+
+        if decompression.total_length + u128::try_from(length).unwrap() > MAX_DECOMPRESSED_LENGTH {
+            cancel_decompression(&batch_id, &commit);
+            return Err("Decompressed content exceeds the maximal length.".to_string());
+        }
+
+        if length > 0 {
+            write_compressed_chunk(
+                batch_id,
+                batch,
+                ASSET_ENCODING_NO_COMPRESSION,
+                &mut commit,
+                &mut decompression.hasher,
+                &mut decompression.total_length,
+                &content[..length],
+            );
+
+            // Persist the progress so that the content already written is known if the decompression does not complete
+            insert_state_commit(&batch_id, &commit);
+        }
+
+        if length < COMPRESSED_CHUNK_LENGTH {
+            break;
+        }
+    }
+
+    let identity = reference_encoding(
+        &decompression.hasher.finalize().into(),
+        std::mem::take(&mut commit.compressed_chunks),
+        std::mem::take(&mut commit.compressed_chunk_lengths),
+        decompression.total_length,
+        true,
+    );
+
+    finish_decompression(batch_id, batch, headers, commit, identity)
+}
+
+// Add the committed encoding and the identity encoding decompressed from it to the asset
+fn finish_decompression(
+    batch_id: u128,
+    batch: &Batch,
+    headers: Vec<HeaderField>,
+    mut commit: BatchCommit,
+    identity: AssetEncoding,
+) -> Result<CommitBatchResult, String> {
     let encoding_type = get_encoding_type(&batch.encoding_type)?;
 
-    let encoding = AssetEncoding::from(&content_chunks);
+    let sha256 = commit
+        .sha256
+        .ok_or_else(|| "Commit hash not found.".to_string())?;
+
+    // The content written is now owned by the blob
+    let encoding = reference_encoding(
+        &sha256,
+        std::mem::take(&mut commit.content_chunks),
+        std::mem::take(&mut commit.content_chunk_lengths),
+        commit.total_length,
+        false,
+    );
+
+    // The content is not decompressed again for another asset
+    if let Some(blob) = get_state_blob(&sha256) {
+        insert_state_blob(
+            &sha256,
+            &StableBlob {
+                decompressed: Some(identity.sha256),
+                ..blob
+            },
+        );
+    }
+
+    insert_committed_asset(
+        batch,
+        headers,
+        vec![
+            (encoding_type, encoding),
+            (ASSET_ENCODING_NO_COMPRESSION.to_string(), identity),
+        ],
+        &[],
+    );
+
+    clear_batch(&batch_id);
+
+    Ok(CommitBatchResult::Done)
+}
+
+// The identity encoding already decompressed from the content, if its blob still exists
+fn reference_decompressed_encoding(sha256: &Hash) -> Option<AssetEncoding> {
+    let decompressed = get_state_blob(sha256)?.decompressed?;
+
+    let blob = get_state_blob(&decompressed)?;
+
+    let total_length: u128 = blob
+        .content_chunk_lengths
+        .iter()
+        .map(|length| u128::try_from(*length).unwrap())
+        .sum();
+
+    Some(reference_encoding(
+        &decompressed,
+        vec![],
+        vec![],
+        total_length,
+        true,
+    ))
+}
+
+// The content written by the commit is not referenced by any blob yet
+fn cancel_decompression(batch_id: &u128, commit: &BatchCommit) {
+    delete_state_content_chunks(&commit.content_chunks);
+    delete_state_content_chunks(&commit.compressed_chunks);
+    clear_batch(batch_id);
+}
+
+fn hash_commit_chunk(batch_id: &u128, content: &[u8]) -> Result<(), String> {
+    let mut hash =
+        get_runtime_hash(batch_id).ok_or_else(|| "Commit hash not found.".to_string())?;
+
+    hash.hasher.update(content);
+
+    insert_runtime_hash(batch_id, hash);
+
+    Ok(())
+}
+
+fn reference_blob(
+    sha256: &Hash,
+    content_chunks: Vec<StableEncodingChunkKey>,
+    content_chunk_lengths: Vec<usize>,
+) -> StableBlob {
+    let blob = match get_state_blob(sha256) {
+        None => StableBlob {
+            content_chunks,
+            content_chunk_lengths,
+            references: 1,
+            decompressed: None,
+        },
+        Some(blob) => {
+            // The same content was written again because its sha256 was only known once all chunks were hashed
+            delete_state_content_chunks(&content_chunks);
 
-    asset.encodings.insert(encoding_type, encoding);
+            StableBlob {
+                references: blob.references + 1,
+                ..blob
+            }
+        }
+    };
 
-    insert_state_asset(&batch.clone().key.full_path, &asset);
+    insert_state_blob(sha256, &blob);
 
-    clear_runtime_batch(&batch_id, &chunk_ids);
+    blob
+}
 
-    Ok(asset)
+// An encoding of the content, which is referenced by its blob
+fn reference_encoding(
+    sha256: &Hash,
+    content_chunks: Vec<StableEncodingChunkKey>,
+    content_chunk_lengths: Vec<usize>,
+    total_length: u128,
+    decompressed: bool,
+) -> AssetEncoding {
+    let StableBlob {
+        content_chunks,
+        content_chunk_lengths,
+        ..
+    } = reference_blob(sha256, content_chunks, content_chunk_lengths);
+
+    AssetEncoding {
+        modified: time(),
+        content_chunks,
+        content_chunk_lengths,
+        total_length,
+        sha256: *sha256,
+        decompressed,
+    }
+}
+
+fn dereference_blob(encoding: &AssetEncoding) {
+    let Some(blob) = get_state_blob(&encoding.sha256) else {
+        return;
+    };
+
+    if blob.references <= 1 {
+        delete_state_content_chunks(&blob.content_chunks);
+        delete_state_blob(&encoding.sha256);
+        return;
+    }
+
+    insert_state_blob(
+        &encoding.sha256,
+        &StableBlob {
+            references: blob.references - 1,
+            ..blob
+        },
+    );
+}
+
+fn init_commit(
+    batch_id: u128,
+    chunk_ids: &[u128],
+    max_size: &Option<u128>,
+) -> Result<BatchCommit, String> {
+    // Collect all chunks
+    let mut chunks: Vec<(u128, u128)> = vec![];
+    let mut size: u128 = 0;
+
+    for chunk_id in chunk_ids.iter() {
+        // Chunks are indexed with their batch, a chunk of another batch is not found
+        let chunk = get_state_chunk(&batch_id, chunk_id);
+
+        match chunk {
+            None => {
+                return Err("Chunk does not exist in the provided batch.".to_string());
+            }
+            Some(c) => {
+                chunks.push((*chunk_id, c.order_id));
+                size += c.content.len() as u128;
+            }
+        }
+    }
+
+    if chunks.is_empty() {
+        return Err("No chunk to commit.".to_string());
+    }
+
+    if max_size.is_some_and(|max_size| size > max_size) {
+        return Err(ERROR_MAX_SIZE_EXCEEDED.to_string());
+    }
+
+    // Sort with ordering
+    chunks.sort_by_key(|(_, order_id)| *order_id);
+
+    let chunk_ids: Vec<u128> = chunks.iter().map(|(chunk_id, _)| *chunk_id).collect();
+
+    // Only the digest has to be finalized if all the chunks to commit were hashed in order while uploaded
+    let hash = get_runtime_hash(&batch_id).filter(|hash| hash.chunk_ids == chunk_ids);
+
+    Ok(match hash {
+        Some(BatchHash {
+            total_length,
+            hasher,
+            ..
+        }) => BatchCommit {
+            chunk_ids,
+            content_chunks: vec![],
+            content_chunk_lengths: vec![],
+            total_length,
+            sha256: Some(hasher.finalize().into()),
+            compressions: vec![],
+            compressed_chunks: vec![],
+            compressed_chunk_lengths: vec![],
+            decompression: false,
+        },
+        None => {
+            // The content is hashed while it is written, in the order of the commit
+            insert_runtime_hash(
+                &batch_id,
+                BatchHash {
+                    chunk_ids: vec![],
+                    last_order_id: None,
+                    total_length: 0,
+                    hasher: Sha256::new(),
+                },
+            );
+
+            BatchCommit {
+                chunk_ids,
+                content_chunks: vec![],
+                content_chunk_lengths: vec![],
+                total_length: 0,
+                sha256: None,
+                compressions: vec![],
+                compressed_chunks: vec![],
+                compressed_chunk_lengths: vec![],
+                decompression: false,
+            }
+        }
+    })
 }
 
 fn get_encoding_type(encoding_type: &Option<String>) -> Result<String, &'static str> {
@@ -357,9 +1836,526 @@ fn get_encoding_type(encoding_type: &Option<String>) -> Result<String, &'static
 }
 
 fn clear_expired_batches() {
-    // Remove expired batches
-    clear_expired_runtime_batches();
+    // Remove expired batches and what was uploaded for these
+    for batch_id in clear_expired_state_batches() {
+        delete_state_chunks(&batch_id);
+        delete_runtime_hash(&batch_id);
+
+        // Remove the content already written in stable memory by a commit or a compression that did not complete
+        if let Some(commit) = delete_state_commit(&batch_id) {
+            delete_state_content_chunks(&commit.content_chunks);
+            delete_state_content_chunks(&commit.compressed_chunks);
+        }
+
+        take_runtime_compression(&batch_id);
+        take_runtime_decompression(&batch_id);
+    }
+}
+
+fn clear_batch(batch_id: &u128) {
+    delete_state_batch(batch_id);
+    delete_state_chunks(batch_id);
+    delete_state_commit(batch_id);
+    delete_runtime_hash(batch_id);
+    take_runtime_compression(batch_id);
+    take_runtime_decompression(batch_id);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::admins::store::add_admin;
+    use crate::memory::STATE;
+    use crate::rules::store::{init_default_rules, set_rule};
+    use crate::rules::types::interface::SetRule;
+    use crate::rules::types::rules::Permission;
+    use crate::shared::api::mock::{
+        certified_data, set_controllers, set_instruction_counter, set_instructions_per_read,
+        set_time,
+    };
+    use crate::storage::constants::LABEL_ASSETS_V1;
+    use crate::types::interface::RulesType;
+
+    fn user() -> Principal {
+        Principal::from_slice(&[1])
+    }
+
+    fn init_asset_key(full_path: &str) -> InitAssetKey {
+        InitAssetKey {
+            name: full_path.rsplit('/').next().unwrap().to_string(),
+            full_path: full_path.to_string(),
+            token: None,
+            collection: "images".to_string(),
+            encoding_type: None,
+            description: None,
+        }
+    }
+
+    // Upload the chunks in the order given, each with its order
+    fn upload(full_path: &str, chunks: &[(&[u8], u128)]) -> (u128, Vec<u128>) {
+        let batch_id = create_batch(user(), init_asset_key(full_path)).unwrap();
+
+        let chunk_ids = chunks
+            .iter()
+            .map(|(content, order_id)| {
+                create_chunk(
+                    user(),
+                    UploadChunk {
+                        batch_id,
+                        content: content.to_vec(),
+                        order_id: Some(*order_id),
+                    },
+                )
+                .unwrap()
+            })
+            .collect();
+
+        (batch_id, chunk_ids)
+    }
+
+    // Each call starts with a new instruction counter
+    fn commit(batch_id: u128, chunk_ids: &[u128]) -> Result<CommitBatchResult, String> {
+        set_instruction_counter(0);
+
+        commit_batch(
+            user(),
+            CommitBatch {
+                batch_id,
+                headers: vec![],
+                chunk_ids: chunk_ids.to_vec(),
+                compress: None,
+            },
+        )
+    }
+
+    fn content(full_path: &str) -> Vec<u8> {
+        let asset = get_state_asset(&full_path.to_string()).unwrap();
+        let encoding = &asset.encodings[ASSET_ENCODING_NO_COMPRESSION];
+
+        (0..encoding.content_chunks.len())
+            .flat_map(|chunk_index| get_content_chunk(encoding, chunk_index).unwrap())
+            .collect()
+    }
+
+    fn count_content_chunks() -> u64 {
+        STATE.with(|state| state.borrow().stable.content_chunks.len())
+    }
+
+    // Two chunks are written per call
+    fn init_commit_threshold() {
+        init_default_rules();
+        set_instructions_per_read(COMMIT_INSTRUCTIONS_THRESHOLD / 2 + 1);
+    }
+
+    #[test]
+    fn commits_the_chunks_over_multiple_calls() {
+        init_commit_threshold();
+
+        let (batch_id, chunk_ids) = upload("/images/a.txt", &[(b"a", 0), (b"b", 1), (b"c", 2)]);
+
+        assert!(matches!(
+            commit(batch_id, &chunk_ids),
+            Ok(CommitBatchResult::Continue {
+                committed_chunks: 2,
+                total_chunks: 3
+            })
+        ));
+        assert!(get_state_asset(&"/images/a.txt".to_string()).is_none());
+
+        assert!(matches!(
+            commit(batch_id, &chunk_ids),
+            Ok(CommitBatchResult::Done)
+        ));
+        assert_eq!(content("/images/a.txt"), b"abc");
+
+        let asset = get_state_asset(&"/images/a.txt".to_string()).unwrap();
+        assert_eq!(
+            asset.encodings[ASSET_ENCODING_NO_COMPRESSION].sha256,
+            sha256(b"abc")
+        );
+
+        // The upload is cleared once committed
+        assert!(get_state_batch(&batch_id).is_none());
+        assert!(get_state_commit(&batch_id).is_none());
+        assert!(get_state_chunk(&batch_id, &chunk_ids[0]).is_none());
+    }
+
+    #[test]
+    fn restarts_a_commit_whose_running_hash_was_lost() {
+        init_commit_threshold();
+
+        // Received out of order, the content is hashed while it is written
+        let (batch_id, chunk_ids) = upload("/images/a.txt", &[(b"c", 2), (b"b", 1), (b"a", 0)]);
+
+        assert!(matches!(
+            commit(batch_id, &chunk_ids),
+            Ok(CommitBatchResult::Continue {
+                committed_chunks: 2,
+                ..
+            })
+        ));
+        assert_eq!(count_content_chunks(), 2);
+
+        // E.g. an upgrade between the calls
+        delete_runtime_hash(&batch_id);
+
+        assert!(matches!(
+            commit(batch_id, &chunk_ids),
+            Ok(CommitBatchResult::Continue {
+                committed_chunks: 2,
+                ..
+            })
+        ));
+        assert!(matches!(
+            commit(batch_id, &chunk_ids),
+            Ok(CommitBatchResult::Done)
+        ));
+
+        assert_eq!(content("/images/a.txt"), b"abc");
+        assert_eq!(
+            get_state_asset(&"/images/a.txt".to_string())
+                .unwrap()
+                .encodings[ASSET_ENCODING_NO_COMPRESSION]
+                .sha256,
+            sha256(b"abc")
+        );
+
+        // The content written before the restart was released
+        assert_eq!(count_content_chunks(), 3);
+    }
+
+    #[test]
+    fn rejects_other_chunks_while_a_commit_is_in_progress() {
+        init_commit_threshold();
+
+        let (batch_id, chunk_ids) = upload("/images/a.txt", &[(b"a", 0), (b"b", 1), (b"c", 2)]);
+
+        assert!(matches!(
+            commit(batch_id, &chunk_ids),
+            Ok(CommitBatchResult::Continue { .. })
+        ));
+
+        assert!(commit(batch_id, &chunk_ids[..2]).is_err());
+        assert!(create_chunk(
+            user(),
+            UploadChunk {
+                batch_id,
+                content: b"d".to_vec(),
+                order_id: Some(3),
+            },
+        )
+        .is_err());
+
+        assert!(matches!(
+            commit(batch_id, &chunk_ids),
+            Ok(CommitBatchResult::Done)
+        ));
+        assert_eq!(content("/images/a.txt"), b"abc");
+    }
+
+    #[test]
+    fn clears_the_upload_of_an_expired_batch() {
+        init_commit_threshold();
+
+        let (batch_id, chunk_ids) = upload("/images/a.txt", &[(b"a", 0), (b"b", 1), (b"c", 2)]);
+
+        assert!(matches!(
+            commit(batch_id, &chunk_ids),
+            Ok(CommitBatchResult::Continue { .. })
+        ));
+
+        // A commit in progress extends the batch
+        set_time(BATCH_EXPIRY_NANOS);
+
+        assert!(matches!(
+            commit(batch_id, &chunk_ids),
+            Ok(CommitBatchResult::Done)
+        ));
 
-    // Remove chunk without existing batches (those we just deleted above)
-    clear_expired_runtime_chunks();
+        let (batch_id, chunk_ids) = upload("/images/b.txt", &[(b"d", 0), (b"e", 1), (b"f", 2)]);
+
+        assert!(matches!(
+            commit(batch_id, &chunk_ids),
+            Ok(CommitBatchResult::Continue { .. })
+        ));
+
+        set_time(3 * BATCH_EXPIRY_NANOS);
+
+        assert!(commit(batch_id, &chunk_ids).is_err());
+
+        assert!(get_state_batch(&batch_id).is_none());
+        assert!(get_state_commit(&batch_id).is_none());
+        assert!(get_state_asset(&"/images/b.txt".to_string()).is_none());
+
+        // Only the content of the asset committed is kept
+        assert_eq!(count_content_chunks(), 3);
+    }
+
+    fn commit_content(full_path: &str, content: &[u8]) {
+        let (batch_id, chunk_ids) = upload(full_path, &[(content, 0)]);
+
+        assert!(matches!(
+            commit(batch_id, &chunk_ids),
+            Ok(CommitBatchResult::Done)
+        ));
+    }
+
+    fn references(content: &[u8]) -> Option<u64> {
+        get_state_blob(&sha256(content)).map(|blob| blob.references)
+    }
+
+    #[test]
+    fn shares_the_content_of_the_same_blob() {
+        init_default_rules();
+
+        commit_content("/images/a.txt", b"content");
+        commit_content("/images/b.txt", b"content");
+
+        assert_eq!(references(b"content"), Some(2));
+        assert_eq!(count_content_chunks(), 1);
+
+        delete_asset(user(), &"images".to_string(), &"/images/a.txt".to_string()).unwrap();
+
+        assert_eq!(references(b"content"), Some(1));
+        assert_eq!(content("/images/b.txt"), b"content");
+
+        delete_asset(user(), &"images".to_string(), &"/images/b.txt".to_string()).unwrap();
+
+        assert_eq!(references(b"content"), None);
+        assert_eq!(count_content_chunks(), 0);
+    }
+
+    #[test]
+    fn releases_the_content_replaced() {
+        init_default_rules();
+
+        commit_content("/images/a.txt", b"before");
+        commit_content("/images/b.txt", b"before");
+        commit_content("/images/a.txt", b"after");
+
+        assert_eq!(references(b"before"), Some(1));
+        assert_eq!(references(b"after"), Some(1));
+
+        commit_content("/images/b.txt", b"after");
+
+        assert_eq!(references(b"before"), None);
+        assert_eq!(references(b"after"), Some(2));
+        assert_eq!(count_content_chunks(), 1);
+        assert_eq!(content("/images/a.txt"), b"after");
+    }
+
+    fn delete(prefix: &str) -> DeleteAssetsResult {
+        set_instruction_counter(0);
+
+        delete_assets(user(), &"images".to_string(), &Some(prefix.to_string())).unwrap()
+    }
+
+    #[test]
+    fn deletes_the_assets_over_multiple_calls() {
+        init_commit_threshold();
+
+        for full_path in ["/images/a/1", "/images/a/2", "/images/a/3", "/images/b/1"] {
+            commit_content(full_path, full_path.as_bytes());
+        }
+
+        // An asset the caller cannot delete is skipped
+        let other = Principal::from_slice(&[2]);
+        let mut asset = get_state_asset(&"/images/a/1".to_string()).unwrap();
+        asset.key.full_path = "/images/a/0".to_string();
+        asset.key.owner = other;
+        asset.encodings.clear();
+        insert_state_asset(&asset.key.full_path, &asset);
+
+        // Three assets are visited per call
+        assert!(matches!(
+            delete("/images/a/"),
+            DeleteAssetsResult::Continue { deleted_assets: 2 }
+        ));
+        assert!(matches!(
+            delete("/images/a/"),
+            DeleteAssetsResult::Done { deleted_assets: 1 }
+        ));
+
+        assert_eq!(
+            get_state_full_paths(),
+            vec!["/images/a/0".to_string(), "/images/b/1".to_string()]
+        );
+        assert_eq!(count_assets(&"images".to_string()), 2);
+    }
+
+    fn set_images_rule(write: Permission, max_size: Option<u128>, max_items: Option<usize>) {
+        set_rule(
+            &RulesType::Storage,
+            &"images".to_string(),
+            SetRule {
+                read: Permission::Public,
+                write,
+                max_size,
+                max_items,
+            },
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn asserts_the_write_permission_of_the_collection() {
+        init_default_rules();
+        set_images_rule(Permission::Managed, None, None);
+
+        let other = Principal::from_slice(&[2]);
+
+        assert_eq!(
+            create_batch(Principal::anonymous(), init_asset_key("/images/a.txt")),
+            Err(ERROR_PERMISSION_DENIED.to_string())
+        );
+
+        commit_content("/images/a.txt", b"a");
+
+        assert_eq!(
+            create_batch(other, init_asset_key("/images/a.txt")),
+            Err(ERROR_PERMISSION_DENIED.to_string())
+        );
+
+        // An admin passes the permission of the collection, the write policy decides whether they replace the asset of its owner
+        add_admin(&other).unwrap();
+
+        assert_eq!(
+            create_batch(other, init_asset_key("/images/a.txt")),
+            Err(ERROR_CANNOT_OVERWRITE_ASSET.to_string())
+        );
+
+        set_write_policy(&WritePolicy::Controllers);
+
+        assert!(create_batch(other, init_asset_key("/images/a.txt")).is_ok());
+
+        set_images_rule(Permission::Controllers, None, None);
+
+        assert_eq!(
+            create_batch(user(), init_asset_key("/images/b.txt")),
+            Err(ERROR_PERMISSION_DENIED.to_string())
+        );
+
+        // The controllers are admins
+        set_controllers(&[user()]);
+
+        assert!(create_batch(user(), init_asset_key("/images/b.txt")).is_ok());
+    }
+
+    #[test]
+    fn limits_the_assets_of_the_collection() {
+        init_default_rules();
+        set_images_rule(Permission::Public, Some(4), Some(1));
+
+        commit_content("/images/a.txt", b"a");
+
+        let (batch_id, chunk_ids) = upload("/images/b.txt", &[(b"b", 0)]);
+        assert!(matches!(
+            commit(batch_id, &chunk_ids),
+            Err(error) if error == ERROR_MAX_ITEMS_REACHED
+        ));
+
+        // An asset can still be replaced
+        commit_content("/images/a.txt", b"aa");
+
+        let (batch_id, chunk_ids) = upload("/images/a.txt", &[(b"aaa", 0), (b"aa", 1)]);
+        assert!(matches!(
+            commit(batch_id, &chunk_ids),
+            Err(error) if error == ERROR_MAX_SIZE_EXCEEDED
+        ));
+
+        assert_eq!(content("/images/a.txt"), b"aa");
+    }
+
+    // The responses of the path are certified for v2 and its content for v1
+    fn is_certified(path: &str) -> bool {
+        STATE.with(|state| {
+            let asset_hashes = &state.borrow().runtime.storage.asset_hashes;
+
+            let v1 = asset_hashes.tree.contains(&[
+                LABEL_ASSETS_V1.as_bytes().to_vec(),
+                path.as_bytes().to_vec(),
+            ]);
+            let v2 = asset_hashes.contains_responses(&exact_expr_path(path));
+
+            assert_eq!(v1, v2);
+
+            v2
+        })
+    }
+
+    fn is_certified_data_up_to_date() -> bool {
+        STATE.with(|state| {
+            certified_data() == state.borrow().runtime.storage.asset_hashes.root_hash()
+        })
+    }
+
+    #[test]
+    fn certifies_the_paths_of_the_assets_committed_and_deleted() {
+        init_default_rules();
+        init_certified_assets();
+
+        assert!(STATE.with(|state| state
+            .borrow()
+            .runtime
+            .storage
+            .asset_hashes
+            .contains_responses(&wildcard_expr_path("/"))));
+
+        commit_content("/images/index.html", b"<html></html>");
+
+        for path in ["/images/index.html", "/images", "/images/"] {
+            assert!(is_certified(path));
+        }
+        assert!(is_certified_data_up_to_date());
+
+        let root_hash = certified_data();
+
+        delete_asset(
+            user(),
+            &"images".to_string(),
+            &"/images/index.html".to_string(),
+        )
+        .unwrap();
+
+        for path in ["/images/index.html", "/images", "/images/"] {
+            assert!(!is_certified(path));
+        }
+        assert!(is_certified_data_up_to_date());
+        assert_ne!(certified_data(), root_hash);
+    }
+
+    #[test]
+    fn certifies_the_assets_readable_by_anyone() {
+        init_default_rules();
+
+        let set_read = |read: Permission| {
+            set_rule(
+                &RulesType::Storage,
+                &"images".to_string(),
+                SetRule {
+                    read,
+                    write: Permission::Public,
+                    max_size: None,
+                    max_items: None,
+                },
+            )
+            .unwrap();
+        };
+
+        set_read(Permission::Managed);
+
+        commit_content("/images/a.txt", b"a");
+
+        assert!(!is_certified("/images/a.txt"));
+
+        set_read(Permission::Public);
+
+        assert!(is_certified("/images/a.txt"));
+        assert!(is_certified_data_up_to_date());
+
+        set_read(Permission::Private);
+
+        assert!(!is_certified("/images/a.txt"));
+        assert!(is_certified_data_up_to_date());
+    }
 }
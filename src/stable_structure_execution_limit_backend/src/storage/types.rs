@@ -1,54 +1,171 @@
 pub mod state {
-    use crate::storage::types::store::{Asset, Batch, Chunk};
-    use crate::types::core::Key;
+    use crate::storage::types::config::{
+        StorageConfig, StorageConfigCorsRule, StorageConfigRedirect,
+    };
+    use crate::storage::types::http::HeaderField;
+    use crate::storage::types::store::{
+        Asset, Batch, BatchCommit, BatchCompression, BatchDecompression, BatchHash, Chunk,
+        DeleteAssetsKey, WritePolicy,
+    };
+    use crate::types::core::{Blob, CollectionKey, Key};
     use crate::types::memory::Memory;
     use candid::CandidType;
-    use ic_stable_structures::StableBTreeMap;
+    use globset::GlobSet;
+    use ic_certified_map::{Hash, RbTree};
+    use ic_stable_structures::{StableBTreeMap, StableCell};
     use serde::{Deserialize, Serialize};
     use std::collections::HashMap;
 
     pub type FullPath = Key;
 
-    pub type Batches = HashMap<u128, Batch>;
-    pub type Chunks = HashMap<u128, Chunk>;
+    pub type Hashes = HashMap<u128, BatchHash>;
+    pub type Compressions = HashMap<u128, BatchCompression>;
+    pub type Decompressions = HashMap<u128, BatchDecompression>;
+    pub type DeleteCursors = HashMap<DeleteAssetsKey, FullPath>;
 
     pub type AssetsStable = StableBTreeMap<StableFullPath, Asset, Memory>;
+    pub type CollectionAssetsStable = StableBTreeMap<StableCollectionAssetKey, (), Memory>;
+    pub type CollectionCountsStable = StableBTreeMap<CollectionKey, u64, Memory>;
+    pub type ContentChunksStable = StableBTreeMap<StableEncodingChunkKey, Blob, Memory>;
+    pub type BlobsStable = StableBTreeMap<Hash, StableBlob, Memory>;
+    pub type BatchesStable = StableBTreeMap<u128, Batch, Memory>;
+    pub type ChunksStable = StableBTreeMap<StableBatchChunkKey, Chunk, Memory>;
+    pub type CommitsStable = StableBTreeMap<u128, BatchCommit, Memory>;
+    pub type UploadIdsStable = StableCell<StableUploadIds, Memory>;
+    pub type WritePolicyStable = StableCell<WritePolicy, Memory>;
+    pub type StorageConfigStable = StableCell<StorageConfig, Memory>;
+
+    // (batch_id, chunk_id) - the chunks are indexed with their batch so that these can be found and removed with a range
+    pub type StableBatchChunkKey = (u128, u128);
 
     #[derive(CandidType, Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
     pub struct StableFullPath {
         pub full_path: FullPath,
     }
 
+    // The full paths of the assets of each collection, ordered by collection. The assets of a collection are found without reading the ones of the others.
+    #[derive(CandidType, Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub struct StableCollectionAssetKey {
+        pub collection: CollectionKey,
+        pub full_path: FullPath,
+    }
+
+    #[derive(CandidType, Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub struct StableEncodingChunkKey {
+        pub full_path: FullPath,
+        pub encoding_type: String,
+        // Chunks are written for a particular batch so that a commit in progress never overwrites the content currently served.
+        // 0 for the chunks written before - ids are allocated from 1.
+        #[serde(default)]
+        pub batch_id: u128,
+        pub chunk_index: usize,
+    }
+
+    // A content saved once in stable memory and shared by all the encodings with the same sha256
+    #[derive(CandidType, Serialize, Deserialize, Clone)]
+    pub struct StableBlob {
+        pub content_chunks: Vec<StableEncodingChunkKey>,
+        // The length of each chunk
+        pub content_chunk_lengths: Vec<usize>,
+        // The number of asset encodings referencing the content
+        pub references: u64,
+        // The sha256 of the identity encoding decompressed from this content, it is not decompressed again for another asset as long as that blob exists
+        #[serde(default)]
+        pub decompressed: Option<Hash>,
+    }
+
+    // The last ids provided to the uploads. Saved with each new id so that the ids keep increasing across upgrades.
+    #[derive(Default, Serialize, Deserialize, Clone)]
+    pub struct StableUploadIds {
+        pub batch_id: u128,
+        pub chunk_id: u128,
+    }
+
+    // A tree whose leaves are at various depths, as the paths of the certification expressions
+    #[derive(Clone)]
+    pub enum NestedTree {
+        Leaf(Vec<u8>),
+        Nested(RbTree<Vec<u8>, NestedTree>),
+    }
+
+    // The hashes certified for the HTTP responses of each path - full paths and their aliases.
+    // Response verification v1 certifies the sha256 of the content under "http_assets", v2 the hashes of the responses - status, headers and body - under the path of their certification expression in "http_expr".
+    #[derive(Default, Clone)]
+    pub struct AssetHashes {
+        pub tree: NestedTree,
+    }
+
+    // The glob patterns of the storage config compiled when the config is set or the canister upgraded, not for each request
+    #[derive(Clone)]
+    pub struct ConfigGlobs<T> {
+        // Sorted from the least to the most specific pattern
+        pub globs: GlobSet,
+        // The value of each glob, in the same order
+        pub values: Vec<T>,
+    }
+
+    #[derive(Default, Clone)]
+    pub struct StorageConfigGlobs {
+        pub headers: ConfigGlobs<Vec<HeaderField>>,
+        // The destination of each rewrite
+        pub rewrites: ConfigGlobs<FullPath>,
+        pub redirects: ConfigGlobs<StorageConfigRedirect>,
+        pub cors: ConfigGlobs<StorageConfigCorsRule>,
+    }
+
+    // The assets saved with a previous format are converted after an upgrade over multiple calls, the cursor is the last full path visited
     #[derive(Default, Clone)]
+    pub struct AssetsMigration {
+        pub cursor: Option<FullPath>,
+    }
+
+    #[derive(Default)]
     pub struct StorageRuntimeState {
-        pub chunks: Chunks,
-        pub batches: Batches,
+        pub hashes: Hashes,
+        pub compressions: Compressions,
+        pub decompressions: Decompressions,
+        pub delete_cursors: DeleteCursors,
+        // None once the assets are converted
+        pub migration: Option<AssetsMigration>,
+        pub asset_hashes: AssetHashes,
+        pub config: StorageConfigGlobs,
     }
 }
 
 pub mod store {
     use crate::storage::types::http::HeaderField;
-    use crate::storage::types::state::FullPath;
+    use crate::storage::types::state::{FullPath, StableEncodingChunkKey};
     use crate::types::core::CollectionKey;
+    use brotli::{CompressorWriter, Decompressor};
     use candid::{CandidType, Principal};
+    use flate2::read::{GzDecoder, ZlibDecoder};
+    use flate2::write::GzEncoder;
     use ic_certified_map::Hash;
     use serde::{Deserialize, Serialize};
+    use sha2::Sha256;
     use std::clone::Clone;
     use std::collections::HashMap;
 
-    #[derive(CandidType, Deserialize, Clone)]
+    #[derive(CandidType, Serialize, Deserialize, Clone)]
     pub struct Chunk {
         pub batch_id: u128,
         pub order_id: u128,
+        #[serde(with = "serde_bytes")]
         pub content: Vec<u8>,
     }
 
     #[derive(CandidType, Serialize, Deserialize, Clone)]
     pub struct AssetEncoding {
         pub modified: u64,
-        pub content_chunks: Vec<Vec<u8>>,
+        // The content is saved in its own stable memory and shared by all encodings with the same sha256, the asset only references its chunks
+        pub content_chunks: Vec<StableEncodingChunkKey>,
+        // The length of each chunk, to find the chunks of a range without reading the content
+        pub content_chunk_lengths: Vec<usize>,
         pub total_length: u128,
         pub sha256: Hash,
+        // The identity encoding was decompressed from a compressed encoding that was uploaded, it is decompressed again when a new one is committed
+        #[serde(default)]
+        pub decompressed: bool,
     }
 
     #[derive(CandidType, Serialize, Deserialize, Clone)]
@@ -76,12 +193,135 @@ pub mod store {
         pub updated_at: u64,
     }
 
-    #[derive(CandidType, Deserialize, Clone)]
+    // Who can replace an existing asset with a new upload at the same full_path
+    #[derive(Default, CandidType, Serialize, Deserialize, Clone, PartialEq)]
+    pub enum WritePolicy {
+        // Only the owner of the asset
+        #[default]
+        Owner,
+        // The owner of the asset and the admins
+        Controllers,
+        // Any caller that is not anonymous
+        Authenticated,
+    }
+
+    #[derive(CandidType, Serialize, Deserialize, Clone)]
     pub struct Batch {
         pub key: AssetKey,
         pub expires_at: u64,
         pub encoding_type: Option<String>,
     }
+
+    // Sha256 of a batch computed while its chunks are uploaded, as long as these are received in order. Otherwise computed while the chunks are committed.
+    #[derive(Clone)]
+    pub struct BatchHash {
+        // The chunks already hashed in the order they were received
+        pub chunk_ids: Vec<u128>,
+        pub last_order_id: Option<u128>,
+        pub total_length: u128,
+        pub hasher: Sha256,
+    }
+
+    // Progress of a commit that is performed over multiple calls to not exceed the instruction limit
+    #[derive(Serialize, Deserialize, Clone)]
+    pub struct BatchCommit {
+        // The chunks to commit sorted with their order
+        pub chunk_ids: Vec<u128>,
+        // The chunks already written in stable memory
+        pub content_chunks: Vec<StableEncodingChunkKey>,
+        pub content_chunk_lengths: Vec<usize>,
+        pub total_length: u128,
+        // The digest computed while uploading. If not available - chunks received out of order - the content is hashed in the running hash of the batch while it is written.
+        pub sha256: Option<Hash>,
+        // The encodings that remain to be derived from the committed identity encoding, the first one is being compressed
+        #[serde(default)]
+        pub compressions: Vec<String>,
+        // The chunks of the encoding being compressed already written in stable memory
+        #[serde(default)]
+        pub compressed_chunks: Vec<StableEncodingChunkKey>,
+        #[serde(default)]
+        pub compressed_chunk_lengths: Vec<usize>,
+        // The identity encoding is being decompressed from the committed content, the asset is made available once it is written in the compressed chunks
+        #[serde(default)]
+        pub decompression: bool,
+    }
+
+    // Compression of the identity encoding of a committed asset into another encoding, performed over multiple calls to not exceed the instruction limit.
+    // The state of the encoder cannot be serialized, it is kept in heap memory.
+    pub struct BatchCompression {
+        pub encoder: ContentEncoder,
+        // The compressed content that does not fill a chunk yet
+        pub output: Vec<u8>,
+        // The next chunk of the identity encoding to compress
+        pub chunk_index: usize,
+        pub total_length: u128,
+        pub hasher: Sha256,
+    }
+
+    pub enum ContentEncoder {
+        Gzip(GzEncoder<Vec<u8>>),
+        Brotli(Box<CompressorWriter<Vec<u8>>>),
+    }
+
+    // Decompression of the committed content into its identity encoding, performed over multiple calls to not exceed the instruction limit.
+    // Like the encoder, the state of the decoder is kept in heap memory.
+    pub struct BatchDecompression {
+        pub decoder: ContentDecoder,
+        pub total_length: u128,
+        pub hasher: Sha256,
+    }
+
+    // The decoders read the compressed content from stable memory, one chunk after the other. The content is decompressed as it is read, a small compressed content cannot fill the heap memory at once.
+    pub enum ContentDecoder {
+        Gzip(GzDecoder<ContentReader>),
+        // The "deflate" content coding is the zlib format
+        Deflate(ZlibDecoder<ContentReader>),
+        Brotli(Box<Decompressor<ContentReader>>),
+    }
+
+    pub struct ContentReader {
+        pub content_chunks: Vec<StableEncodingChunkKey>,
+        // The next chunk to read
+        pub chunk_index: usize,
+        // The chunk being read and the length already read
+        pub chunk: Vec<u8>,
+        pub offset: usize,
+    }
+
+    // The deletions in progress are identified by their caller and what they delete - the one of a caller does not resume where the one of another caller stopped
+    #[derive(Clone, PartialEq, Eq, Hash)]
+    pub struct DeleteAssetsKey {
+        pub caller: Principal,
+        pub collection: CollectionKey,
+        pub prefix: Option<FullPath>,
+    }
+}
+
+// The formats saved in stable memory before the values were versioned. Only decoded to be migrated.
+pub mod legacy {
+    use crate::storage::types::http::HeaderField;
+    use crate::storage::types::store::AssetKey;
+    use ic_certified_map::Hash;
+    use serde::{Deserialize, Serialize};
+    use std::collections::HashMap;
+
+    // The content was saved inline in the asset
+    #[derive(Serialize, Deserialize)]
+    pub struct LegacyAssetEncoding {
+        pub modified: u64,
+        pub content_chunks: Vec<Vec<u8>>,
+        pub total_length: u128,
+        pub sha256: Hash,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct LegacyAsset {
+        pub key: AssetKey,
+        pub headers: Vec<HeaderField>,
+        pub encodings: HashMap<String, LegacyAssetEncoding>,
+        pub created_at: u64,
+        pub updated_at: u64,
+    }
 }
 
 pub mod interface {
@@ -125,6 +365,36 @@ pub mod interface {
         pub batch_id: u128,
         pub headers: Vec<HeaderField>,
         pub chunk_ids: Vec<u128>,
+        // Derive the compressed encodings of an identity encoding in the canister. The commit continues until these are written.
+        pub compress: Option<bool>,
+    }
+
+    #[derive(CandidType)]
+    pub enum CommitBatchResult {
+        // The asset has been committed and is available
+        Done,
+        // The instruction limit was nearly reached, the commit should be called again with the same parameters.
+        // The chunks count the ones of the compressed encodings - if requested - which are compressed once the asset is available.
+        Continue {
+            committed_chunks: usize,
+            total_chunks: usize,
+        },
+    }
+
+    #[derive(CandidType)]
+    pub enum DeleteAssetsResult {
+        // All the assets have been deleted
+        Done { deleted_assets: usize },
+        // The instruction limit was nearly reached, the deletion should be called again to delete the remaining assets
+        Continue { deleted_assets: usize },
+    }
+
+    #[derive(CandidType)]
+    pub enum MigrateAssetsResult {
+        // All the assets saved with a previous format have been converted
+        Done { migrated_assets: usize },
+        // The instruction limit was nearly reached, the migration should be called again to convert the remaining assets
+        Continue { migrated_assets: usize },
     }
 
     #[derive(CandidType, Deserialize, Clone)]
@@ -158,6 +428,7 @@ pub mod http {
         pub method: String,
         pub headers: Vec<HeaderField>,
         pub body: Vec<u8>,
+        pub certificate_version: Option<u16>,
     }
 
     #[derive(CandidType, Deserialize, Clone)]
@@ -166,6 +437,8 @@ pub mod http {
         pub headers: Vec<HeaderField>,
         pub status_code: u16,
         pub streaming_strategy: Option<StreamingStrategy>,
+        // The request should be sent again as an update call to http_request_update
+        pub upgrade: Option<bool>,
     }
 
     define_function!(pub CallbackFunc : () -> () query);
@@ -193,6 +466,12 @@ pub mod http {
         pub body: Vec<u8>,
         pub token: Option<StreamingCallbackToken>,
     }
+    // A range of bytes of a "Range" request header, start and end included
+    #[derive(Clone, PartialEq, Eq)]
+    pub struct ByteRange {
+        pub start: u128,
+        pub end: u128,
+    }
 }
 
 pub mod config {
@@ -203,11 +482,35 @@ pub mod config {
 
     pub type StorageConfigHeaders = HashMap<String, Vec<HeaderField>>;
     pub type StorageConfigRewrites = HashMap<String, String>;
+    pub type StorageConfigRedirects = HashMap<String, StorageConfigRedirect>;
+    pub type StorageConfigErrorPages = HashMap<u16, String>;
+    pub type StorageConfigCors = HashMap<String, StorageConfigCorsRule>;
 
     #[derive(Default, CandidType, Serialize, Deserialize, Clone)]
     pub struct StorageConfig {
         pub headers: StorageConfigHeaders,
         pub rewrites: StorageConfigRewrites,
+        pub redirects: StorageConfigRedirects,
+        // The path of the asset served for an error status code - e.g. 404 -> /404.html
+        pub error_pages: StorageConfigErrorPages,
+        pub cors: StorageConfigCors,
+    }
+
+    // The origins allowed to fetch the assets of a path from another origin, "*" for any origin
+    #[derive(CandidType, Serialize, Deserialize, Clone)]
+    pub struct StorageConfigCorsRule {
+        pub allow_origins: Vec<String>,
+        // The methods answered to a preflight request, all the supported methods if empty
+        pub allow_methods: Vec<String>,
+        pub allow_headers: Vec<String>,
+        pub expose_headers: Vec<String>,
+        pub max_age: Option<u32>,
+    }
+
+    #[derive(CandidType, Serialize, Deserialize, Clone)]
+    pub struct StorageConfigRedirect {
+        pub location: String,
+        pub status_code: u16,
     }
 }
 
@@ -225,5 +528,7 @@ pub mod http_request {
     pub struct PublicAsset {
         pub url: String,
         pub asset: Option<Asset>,
+        // The asset is the destination of a rewrite of the url
+        pub rewritten: bool,
     }
 }
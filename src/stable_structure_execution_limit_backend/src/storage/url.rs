@@ -2,12 +2,12 @@ use crate::storage::types::http_request::MapUrl;
 use std::path::Path;
 use url::{ParseError, Url};
 
-pub fn map_url(url: &String) -> Result<MapUrl, &'static str> {
+pub fn map_url(url: &str) -> Result<MapUrl, &'static str> {
     let parsed_url = build_url(url);
 
     match parsed_url {
         Err(_) => {
-            let error = format!("Url {} cannot be parsed.", url.clone()).into_boxed_str();
+            let error = format!("Url {} cannot be parsed.", url).into_boxed_str();
             Err(Box::leak(error))
         }
         Ok(parsed_url) => {
@@ -24,7 +24,7 @@ pub fn map_url(url: &String) -> Result<MapUrl, &'static str> {
     }
 }
 
-pub fn map_alternative_paths(path: &String) -> Vec<String> {
+pub fn map_alternative_paths(path: &str) -> Vec<String> {
     // The requested path is /something.js or without file extension (/something or /something/)?
     let extension = Path::new(path).extension();
 
@@ -33,15 +33,15 @@ pub fn map_alternative_paths(path: &String) -> Vec<String> {
         Some(_) => Vec::new(),
         None => {
             // Url has no extension - e.g. is not something.js but /about or /about/
-            aliases_of(&path.to_string())
+            aliases_of(path)
         }
     }
 }
 
-/// BEGIN: From DFINITY certified asset canister
+// BEGIN: From DFINITY certified asset canister
 
 // path like /path/to/my/asset should also be valid for /path/to/my/asset.html or /path/to/my/asset/index.html
-fn aliases_of(key: &String) -> Vec<String> {
+fn aliases_of(key: &str) -> Vec<String> {
     if key.ends_with('/') {
         vec![format!("{}index.html", key)]
     } else if !key.ends_with(".html") {
@@ -51,9 +51,34 @@ fn aliases_of(key: &String) -> Vec<String> {
     }
 }
 
-/// END
+// path like /path/to/my/asset/index.html or /path/to/my/asset.html is also served for /path/to/my/asset
+pub fn aliased_by(key: &str) -> Vec<String> {
+    if key == "/index.html" {
+        vec!["/".to_string()]
+    } else if let Some(path) = key.strip_suffix("/index.html") {
+        vec![path.to_string(), format!("{}/", path)]
+    } else if let Some(path) = key.strip_suffix(".html") {
+        vec![path.to_string()]
+    } else {
+        Vec::new()
+    }
+}
+
+// END
+
+// The directory of a glob pattern before its first wildcard - e.g. /assets/** -> /assets/ or /**/*.js -> /
+pub fn glob_prefix(pattern: &str) -> String {
+    let literal = pattern
+        .find(['*', '?', '[', '{'])
+        .map_or(pattern, |index| &pattern[..index]);
+
+    match literal.rfind('/') {
+        Some(index) => literal[..=index].to_string(),
+        None => "/".to_string(),
+    }
+}
 
-pub fn build_url(url: &String) -> Result<Url, ParseError> {
+pub fn build_url(url: &str) -> Result<Url, ParseError> {
     let separator = separator(url);
 
     Url::parse(&["http://localhost", separator, url].join(""))
@@ -1,6 +1,12 @@
 pub mod state {
+    use crate::admins::types::state::AdminsStable;
     use crate::memory::init_stable_state;
-    use crate::storage::types::state::{AssetsStable, StorageRuntimeState};
+    use crate::rules::types::state::RulesStable;
+    use crate::storage::types::state::{
+        AssetsStable, BatchesStable, BlobsStable, ChunksStable, CollectionAssetsStable,
+        CollectionCountsStable, CommitsStable, ContentChunksStable, StorageConfigStable,
+        StorageRuntimeState, UploadIdsStable, WritePolicyStable,
+    };
     use serde::{Deserialize, Serialize};
 
     #[derive(Serialize, Deserialize)]
@@ -15,9 +21,22 @@ pub mod state {
 
     pub struct StableState {
         pub assets: AssetsStable,
+        pub content_chunks: ContentChunksStable,
+        pub blobs: BlobsStable,
+        pub batches: BatchesStable,
+        pub chunks: ChunksStable,
+        pub commits: CommitsStable,
+        pub upload_ids: UploadIdsStable,
+        pub write_policy: WritePolicyStable,
+        pub rules: RulesStable,
+        pub admins: AdminsStable,
+        pub storage_config: StorageConfigStable,
+        // The assets of each collection and their number, maintained when an asset is inserted or deleted
+        pub collection_assets: CollectionAssetsStable,
+        pub collection_counts: CollectionCountsStable,
     }
 
-    #[derive(Default, Clone)]
+    #[derive(Default)]
     pub struct RuntimeState {
         pub storage: StorageRuntimeState,
     }
@@ -28,6 +47,7 @@ pub mod core {
 
     pub type Key = String;
     pub type CollectionKey = String;
+    pub type Blob = Vec<u8>;
 
     pub trait Compare {
         fn cmp_updated_at(&self, other: &Self) -> Ordering;